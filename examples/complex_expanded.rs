@@ -3,8 +3,6 @@
 /// and does not expand irrelevant parts of the code (e.g. `#[derive(Debug)]`, etc.)
 ///
 /// This file serves the purpose of revealing what's happening behind the curtains.
-use core::marker::PhantomData;
-
 #[derive(Debug)]
 struct Player {
     race: Race,
@@ -20,47 +18,120 @@ enum Race {
     Human,
 }
 
+mod sealed_player_builder {
+    pub trait Sealed {}
+}
+
+pub trait StateNamePlayerBuilder {
+    const NAME: &'static str;
+}
+
+// the derived traits of the markers are required here, so that they are also available for the generic states
+pub trait SealerPlayerBuilder:
+    sealed_player_builder::Sealed
+    + StateNamePlayerBuilder
+    + ::core::clone::Clone
+    + ::core::marker::Copy
+    + ::core::fmt::Debug
+    + ::core::cmp::PartialEq
+    + ::core::cmp::Eq
+    + ::core::hash::Hash
+    + ::core::default::Default
+{
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Initial;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct RaceSet;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct LevelSet;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct SkillSlotsSet;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct SpellSlotsSet;
+
+impl sealed_player_builder::Sealed for Initial {}
+impl sealed_player_builder::Sealed for RaceSet {}
+impl sealed_player_builder::Sealed for LevelSet {}
+impl sealed_player_builder::Sealed for SkillSlotsSet {}
+impl sealed_player_builder::Sealed for SpellSlotsSet {}
+
+impl SealerPlayerBuilder for Initial {}
+impl SealerPlayerBuilder for RaceSet {}
+impl SealerPlayerBuilder for LevelSet {}
+impl SealerPlayerBuilder for SkillSlotsSet {}
+impl SealerPlayerBuilder for SpellSlotsSet {}
+
+impl StateNamePlayerBuilder for Initial {
+    const NAME: &'static str = "Initial";
+}
+impl StateNamePlayerBuilder for RaceSet {
+    const NAME: &'static str = "RaceSet";
+}
+impl StateNamePlayerBuilder for LevelSet {
+    const NAME: &'static str = "LevelSet";
+}
+impl StateNamePlayerBuilder for SkillSlotsSet {
+    const NAME: &'static str = "SkillSlotsSet";
+}
+impl StateNamePlayerBuilder for SpellSlotsSet {
+    const NAME: &'static str = "SpellSlotsSet";
+}
+
+/// Implemented by each `#[impl_state]` block of `PlayerBuilder`, a conflicting implementation means that a block with `runtime`, `dot`, `mermaid`, `json` or `reachability` is not the only one
+#[doc(hidden)]
+pub trait SingleImplStateBlockPlayerBuilder<Block> {}
+
+#[must_use = "state transition result must be used"]
 #[allow(clippy::type_complexity)]
-struct PlayerBuilder<State1 = Initial, State2 = Initial, State3 = Initial>
-where
-    State1: TypeStateProtector,
-    State2: TypeStateProtector,
-    State3: TypeStateProtector,
+struct PlayerBuilder<
+    PlayerBuilderState1 = Initial,
+    PlayerBuilderState2 = Initial,
+    PlayerBuilderState3 = Initial,
+> where
+    PlayerBuilderState1: SealerPlayerBuilder,
+    PlayerBuilderState2: SealerPlayerBuilder,
+    PlayerBuilderState3: SealerPlayerBuilder,
 {
     race: Option<Race>,
     level: Option<u8>,
     skill_slots: Option<u8>,
     spell_slots: Option<u8>,
     _state: (
-        PhantomData<fn() -> State1>,
-        PhantomData<fn() -> State2>,
-        PhantomData<fn() -> State3>,
+        ::core::marker::PhantomData<fn() -> PlayerBuilderState1>,
+        ::core::marker::PhantomData<fn() -> PlayerBuilderState2>,
+        ::core::marker::PhantomData<fn() -> PlayerBuilderState3>,
     ),
 }
 
-mod sealed {
-    pub trait Sealed {}
+// `#[impl_state]` is expanded through this macro, which passes it the declaration of the struct
+// (the states, the slots...), so that the `impl` blocks don't have to repeat them
+#[doc(hidden)]
+#[allow(unused_macros)]
+macro_rules! __state_shift_PlayerBuilder {
+    ($($input:tt)*) => {
+        ::state_shift::__impl_state_with_info! {
+            {
+                (["Initial", "RaceSet", "LevelSet", "SkillSlotsSet", "SpellSlotsSet"],
+                [(None, "Initial", None, false),
+                (None, "Initial", None, false),
+                (None, "Initial", None, false)], None, "", None, false, [],
+                [], [])
+            } $($input)*
+        }
+    };
 }
+#[doc(hidden)]
+#[allow(unused_imports)]
+pub(crate) use __state_shift_PlayerBuilder as PlayerBuilder;
 
-pub trait TypeStateProtector: sealed::Sealed {}
-
-struct Initial;
-struct RaceSet;
-struct LevelSet;
-struct SkillSlotsSet;
-struct SpellSlotsSet;
-
-impl sealed::Sealed for Initial {}
-impl sealed::Sealed for RaceSet {}
-impl sealed::Sealed for LevelSet {}
-impl sealed::Sealed for SkillSlotsSet {}
-impl sealed::Sealed for SpellSlotsSet {}
-
-impl TypeStateProtector for Initial {}
-impl TypeStateProtector for RaceSet {}
-impl TypeStateProtector for LevelSet {}
-impl TypeStateProtector for SkillSlotsSet {}
-impl TypeStateProtector for SpellSlotsSet {}
+// this block doesn't need all of the methods of the struct, so it can be one of many
+const _: () = {
+    #[allow(dead_code)]
+    struct Block;
+    impl SingleImplStateBlockPlayerBuilder<Block> for () {}
+};
 
 impl PlayerBuilder<Initial, Initial, Initial> {
     fn new() -> Self {
@@ -69,111 +140,123 @@ impl PlayerBuilder<Initial, Initial, Initial> {
             level: None,
             skill_slots: None,
             spell_slots: None,
-            _state: (PhantomData, PhantomData, PhantomData),
+            _state: (
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+            ),
         }
     }
 }
+
 impl<B, C> PlayerBuilder<Initial, B, C>
 where
-    B: TypeStateProtector,
-    C: TypeStateProtector,
+    B: SealerPlayerBuilder,
+    C: SealerPlayerBuilder,
 {
     fn set_race(self, race: Race) -> PlayerBuilder<RaceSet, B, C> {
-        {
-            {
-                PlayerBuilder {
-                    race: Some(race),
-                    level: self.level,
-                    skill_slots: self.skill_slots,
-                    spell_slots: self.spell_slots,
-                    _state: (PhantomData, PhantomData, PhantomData),
-                }
-            }
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+            skill_slots: self.skill_slots,
+            spell_slots: self.spell_slots,
+            _state: (
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+            ),
         }
     }
 }
+
 impl<B, C> PlayerBuilder<RaceSet, B, C>
 where
-    B: TypeStateProtector,
-    C: TypeStateProtector,
+    B: SealerPlayerBuilder,
+    C: SealerPlayerBuilder,
 {
     fn set_level(self, level_modifier: u8) -> PlayerBuilder<RaceSet, LevelSet, C> {
-        {
-            {
-                let level = match self.race {
-                    Some(Race::Orc) => level_modifier + 2,
-                    Some(Race::Human) => level_modifier,
-                    None => unreachable!("type safety ensures that `race` is initialized"),
-                };
-                PlayerBuilder {
-                    race: self.race,
-                    level: Some(level),
-                    skill_slots: self.skill_slots,
-                    spell_slots: self.spell_slots,
-                    _state: (PhantomData, PhantomData, PhantomData),
-                }
-            }
+        let level = match self.race {
+            Some(Race::Orc) => level_modifier + 2, // Orc's have +2 level advantage
+            Some(Race::Human) => level_modifier,   // humans are weak
+            None => unreachable!("type safety ensures that `race` is initialized"),
+        };
+
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+            skill_slots: self.skill_slots,
+            spell_slots: self.spell_slots,
+            _state: (
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+            ),
         }
     }
 }
+
 impl<B, C> PlayerBuilder<RaceSet, B, C>
 where
-    B: TypeStateProtector,
-    C: TypeStateProtector,
+    B: SealerPlayerBuilder,
+    C: SealerPlayerBuilder,
 {
     fn set_skill_slots(self, skill_slot_modifier: u8) -> PlayerBuilder<RaceSet, B, SkillSlotsSet> {
-        {
-            {
-                let skill_slots = match self.race {
-                    Some(Race::Orc) => skill_slot_modifier,
-                    Some(Race::Human) => skill_slot_modifier + 1,
-                    None => unreachable!("type safety ensures that `race` should be initialized"),
-                };
-                PlayerBuilder {
-                    race: self.race,
-                    level: self.level,
-                    skill_slots: Some(skill_slots),
-                    spell_slots: self.spell_slots,
-                    _state: (PhantomData, PhantomData, PhantomData),
-                }
-            }
+        let skill_slots = match self.race {
+            Some(Race::Orc) => skill_slot_modifier,
+            Some(Race::Human) => skill_slot_modifier + 1, // Human's have +1 skill slot advantage
+            None => unreachable!("type safety ensures that `race` should be initialized"),
+        };
+
+        PlayerBuilder {
+            race: self.race,
+            level: self.level,
+            skill_slots: Some(skill_slots),
+            spell_slots: self.spell_slots,
+            _state: (
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+            ),
         }
     }
 }
+
 impl<A> PlayerBuilder<A, LevelSet, SkillSlotsSet>
 where
-    A: TypeStateProtector,
+    A: SealerPlayerBuilder,
 {
     fn set_spells(
         self,
         spell_slot_modifier: u8,
     ) -> PlayerBuilder<SpellSlotsSet, LevelSet, SkillSlotsSet> {
-        {
-            {
-                let level = self
-                    .level
-                    .expect("type safety ensures that `level` is initialized");
-                let skill_slots = self
-                    .skill_slots
-                    .as_ref()
-                    .expect("type safety ensures that `skill_slots` is initialized");
-                let spell_slots = level / 10 + skill_slots + spell_slot_modifier;
-                PlayerBuilder {
-                    race: self.race,
-                    level: self.level,
-                    skill_slots: self.skill_slots,
-                    spell_slots: Some(spell_slots),
-                    _state: (PhantomData, PhantomData, PhantomData),
-                }
-            }
+        let level = self
+            .level
+            .expect("type safety ensures that `level` is initialized");
+        let skill_slots = self
+            .skill_slots
+            .as_ref()
+            .expect("type safety ensures that `skill_slots` is initialized");
+        let spell_slots = level / 10 + skill_slots + spell_slot_modifier;
+
+        PlayerBuilder {
+            race: self.race,
+            level: self.level,
+            skill_slots: self.skill_slots,
+            spell_slots: Some(spell_slots),
+            _state: (
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+                ::core::marker::PhantomData,
+            ),
         }
     }
 }
+
 impl<A, B, C> PlayerBuilder<A, B, C>
 where
-    A: TypeStateProtector,
-    B: TypeStateProtector,
-    C: TypeStateProtector,
+    A: SealerPlayerBuilder,
+    B: SealerPlayerBuilder,
+    C: SealerPlayerBuilder,
 {
     fn say_hi(self) -> Self {
         println!("Hi!");
@@ -181,19 +264,18 @@ where
         self
     }
 }
+
 impl<B, C> PlayerBuilder<SpellSlotsSet, B, C>
 where
-    B: TypeStateProtector,
-    C: TypeStateProtector,
+    B: SealerPlayerBuilder,
+    C: SealerPlayerBuilder,
 {
     fn build(self) -> Player {
-        {
-            Player {
-                race: self.race.expect("type safety ensures this is set"),
-                level: self.level.expect("type safety ensures this is set"),
-                skill_slots: self.skill_slots.expect("type safety ensures this is set"),
-                spell_slots: self.spell_slots.expect("type safety ensures this is set"),
-            }
+        Player {
+            race: self.race.expect("type safety ensures this is set"),
+            level: self.level.expect("type safety ensures this is set"),
+            skill_slots: self.skill_slots.expect("type safety ensures this is set"),
+            spell_slots: self.spell_slots.expect("type safety ensures this is set"),
         }
     }
 }
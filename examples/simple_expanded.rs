@@ -3,8 +3,6 @@
 /// and does not expand irrelevant parts of the code (e.g. `#[derive(Debug)]`, etc.)
 ///
 /// This file serves the purpose of revealing what's happening behind the curtains.
-use core::marker::PhantomData;
-
 #[derive(Debug)]
 struct Player {
     race: Race,
@@ -19,42 +17,117 @@ enum Race {
     Human,
 }
 
-struct PlayerBuilder<State1 = Initial> {
+mod sealed_player_builder {
+    pub trait Sealed {}
+}
+
+pub trait StateNamePlayerBuilder {
+    const NAME: &'static str;
+}
+
+// the derived traits of the markers are required here, so that they are also available for the generic states
+pub trait SealerPlayerBuilder:
+    sealed_player_builder::Sealed
+    + StateNamePlayerBuilder
+    + ::core::clone::Clone
+    + ::core::marker::Copy
+    + ::core::fmt::Debug
+    + ::core::cmp::PartialEq
+    + ::core::cmp::Eq
+    + ::core::hash::Hash
+    + ::core::default::Default
+{
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Initial;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct RaceSet;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct LevelSet;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct SkillSlotsSet;
+
+impl sealed_player_builder::Sealed for Initial {}
+impl sealed_player_builder::Sealed for RaceSet {}
+impl sealed_player_builder::Sealed for LevelSet {}
+impl sealed_player_builder::Sealed for SkillSlotsSet {}
+
+impl SealerPlayerBuilder for Initial {}
+impl SealerPlayerBuilder for RaceSet {}
+impl SealerPlayerBuilder for LevelSet {}
+impl SealerPlayerBuilder for SkillSlotsSet {}
+
+impl StateNamePlayerBuilder for Initial {
+    const NAME: &'static str = "Initial";
+}
+impl StateNamePlayerBuilder for RaceSet {
+    const NAME: &'static str = "RaceSet";
+}
+impl StateNamePlayerBuilder for LevelSet {
+    const NAME: &'static str = "LevelSet";
+}
+impl StateNamePlayerBuilder for SkillSlotsSet {
+    const NAME: &'static str = "SkillSlotsSet";
+}
+
+/// Implemented by each `#[impl_state]` block of `PlayerBuilder`, a conflicting implementation means that a block with `runtime`, `dot`, `mermaid`, `json` or `reachability` is not the only one
+#[doc(hidden)]
+pub trait SingleImplStateBlockPlayerBuilder<Block> {}
+
+#[must_use = "state transition result must be used"]
+#[allow(clippy::type_complexity)]
+struct PlayerBuilder<PlayerBuilderState1 = Initial>
+where
+    PlayerBuilderState1: SealerPlayerBuilder,
+{
     race: Option<Race>,
     level: Option<u8>,
     skill_slots: Option<u8>,
-    #[allow(unused_parens)]
-    _state: PhantomData<fn() -> State1>,
+    _state: ::core::marker::PhantomData<fn() -> PlayerBuilderState1>,
 }
 
-mod sealed {
-    pub trait Sealed {}
+// `state_names = true`
+impl<PlayerBuilderState1: SealerPlayerBuilder> PlayerBuilder<PlayerBuilderState1> {
+    /// Returns the names of the current states of the slots, without any runtime cost
+    pub fn state_names(&self) -> [&'static str; 1usize] {
+        [<PlayerBuilderState1 as StateNamePlayerBuilder>::NAME]
+    }
 }
 
-pub trait TypeStateProtector: sealed::Sealed {}
-
-struct Initial;
-struct RaceSet;
-struct LevelSet;
-struct SkillSlotsSet;
-
-impl sealed::Sealed for Initial {}
-impl sealed::Sealed for RaceSet {}
-impl sealed::Sealed for LevelSet {}
-impl sealed::Sealed for SkillSlotsSet {}
+// `#[impl_state]` is expanded through this macro, which passes it the declaration of the struct
+// (the states, the slots...), so that the `impl` blocks don't have to repeat them
+#[doc(hidden)]
+#[allow(unused_macros)]
+macro_rules! __state_shift_PlayerBuilder {
+    ($($input:tt)*) => {
+        ::state_shift::__impl_state_with_info! {
+            {
+                (["Initial", "RaceSet", "LevelSet", "SkillSlotsSet"],
+                [(None, "Initial", None, false)], None, "", None, false, [],
+                [], [])
+            } $($input)*
+        }
+    };
+}
+#[doc(hidden)]
+#[allow(unused_imports)]
+pub(crate) use __state_shift_PlayerBuilder as PlayerBuilder;
 
-impl TypeStateProtector for Initial {}
-impl TypeStateProtector for RaceSet {}
-impl TypeStateProtector for LevelSet {}
-impl TypeStateProtector for SkillSlotsSet {}
+// this block doesn't need all of the methods of the struct, so it can be one of many
+const _: () = {
+    #[allow(dead_code)]
+    struct Block;
+    impl SingleImplStateBlockPlayerBuilder<Block> for () {}
+};
 
 impl PlayerBuilder<Initial> {
-    fn new() -> Self {
+    fn new() -> PlayerBuilder<Initial> {
         PlayerBuilder {
             race: None,
             level: None,
             skill_slots: None,
-            _state: (PhantomData),
+            _state: ::core::marker::PhantomData,
         }
     }
 }
@@ -65,10 +138,11 @@ impl PlayerBuilder<Initial> {
             race: Some(race),
             level: self.level,
             skill_slots: self.skill_slots,
-            _state: (PhantomData),
+            _state: ::core::marker::PhantomData,
         }
     }
 }
+
 impl PlayerBuilder<RaceSet> {
     fn set_level(self, level_modifier: u8) -> PlayerBuilder<LevelSet> {
         let level = match self.race {
@@ -81,10 +155,11 @@ impl PlayerBuilder<RaceSet> {
             race: self.race,
             level: Some(level),
             skill_slots: self.skill_slots,
-            _state: (PhantomData),
+            _state: ::core::marker::PhantomData,
         }
     }
 }
+
 impl PlayerBuilder<LevelSet> {
     fn set_skill_slots(self, skill_slot_modifier: u8) -> PlayerBuilder<SkillSlotsSet> {
         let skill_slots = match self.race {
@@ -97,13 +172,14 @@ impl PlayerBuilder<LevelSet> {
             race: self.race,
             level: self.level,
             skill_slots: Some(skill_slots),
-            _state: (PhantomData),
+            _state: ::core::marker::PhantomData,
         }
     }
 }
+
 impl<A> PlayerBuilder<A>
 where
-    A: TypeStateProtector,
+    A: SealerPlayerBuilder,
 {
     fn say_hi(self) -> Self {
         println!("Hi!");
@@ -111,6 +187,7 @@ where
         self
     }
 }
+
 impl PlayerBuilder<SkillSlotsSet> {
     fn build(self) -> Player {
         Player {
@@ -132,4 +209,5 @@ fn main() {
     println!("Race: {:?}", player.race);
     println!("Level: {}", player.level);
     println!("Skill slots: {}", player.skill_slots);
+    println!("States: {:?}", PlayerBuilder::new().state_names());
 }
@@ -0,0 +1,339 @@
+// this file contains the logic that generates the type-erased `Any{StructName}` enum for `#[type_state(any = true)]`,
+// so that the objects in different states can be stored together (e.g. in a `Vec` or a `HashMap`),
+// and the `{StructName}State` enum, which mirrors the states at runtime
+use proc_macro2::TokenStream;
+use quote::quote;
+use stringcase::snake_case;
+use syn::{Generics, Ident, Path, Visibility};
+
+use crate::generic_args;
+
+/// The name of the variant of `Any{StructName}` for a state combination: `RaceSet`, or `RaceSetLevelUnset` for multiple slots
+pub fn any_variant_ident(states: &[Ident]) -> Ident {
+    let name: String = states.iter().map(|state| state.to_string()).collect();
+    Ident::new(&name, states[0].span())
+}
+
+pub fn generate_any_enum(
+    struct_name: &Ident,
+    visibility: &Visibility,
+    generics: &Generics,
+    state_combinations: &[Vec<Ident>],
+    serde_derives: &[Path],
+) -> TokenStream {
+    let any_name = Ident::new(&format!("Any{}", struct_name), struct_name.span());
+    let state_enum_name = Ident::new(&format!("{}State", struct_name), struct_name.span());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let original_args = generic_args(generics);
+    let slot_count = state_combinations.first().map_or(0, |states| states.len());
+
+    let variants: Vec<Ident> = state_combinations
+        .iter()
+        .map(|states| any_variant_ident(states))
+        .collect();
+
+    // `PlayerBuilder<'a, T, RaceSet, LevelUnset>`
+    let typed_structs: Vec<TokenStream> = state_combinations
+        .iter()
+        .map(|states| quote!(#struct_name<#(#original_args,)* #(#states),*>))
+        .collect();
+
+    // `["RaceSet", "LevelUnset"]`
+    let state_names: Vec<TokenStream> = state_combinations
+        .iter()
+        .map(|states| {
+            let names = states.iter().map(|state| state.to_string());
+            quote!([#(#names),*])
+        })
+        .collect();
+
+    // `try_into_race_set_level_unset()`
+    let downcasts: Vec<TokenStream> = variants
+        .iter()
+        .zip(&typed_structs)
+        .map(|(variant, typed_struct)| {
+            let fn_name = Ident::new(
+                &format!("try_into_{}", snake_case(&variant.to_string())),
+                variant.span(),
+            );
+            let doc = format!(
+                "Returns the typed object if it is in the `{}` state, or gives back `self` otherwise",
+                variant
+            );
+            quote! {
+                #[doc = #doc]
+                #[allow(unreachable_patterns)]
+                pub fn #fn_name(self) -> ::core::result::Result<#typed_struct, Self> {
+                    match self {
+                        Self::#variant(value) => ::core::result::Result::Ok(value),
+                        other => ::core::result::Result::Err(other),
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let from_impls: Vec<TokenStream> = variants
+        .iter()
+        .zip(&typed_structs)
+        .map(|(variant, typed_struct)| {
+            quote! {
+                impl #impl_generics ::core::convert::From<#typed_struct> for #any_name #ty_generics #where_clause {
+                    fn from(value: #typed_struct) -> Self {
+                        Self::#variant(value)
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let doc = format!(
+        "Type-erased `{}`: holds the object in any of its reachable states",
+        struct_name
+    );
+    let state_enum_doc = format!(
+        "The runtime mirror of the states of `{}`, without the object",
+        struct_name
+    );
+
+    // the state is stored as a tag: `{ "state": "RaceSet", "race": "Orc", ... }`,
+    // so that only the reachable states can be deserialized
+    let serde_attrs = if serde_derives.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            #[derive(#(#serde_derives),*)]
+            #[serde(tag = "state")]
+        }
+    };
+
+    quote! {
+        #[doc = #doc]
+        #[allow(clippy::type_complexity)]
+        #serde_attrs
+        #visibility enum #any_name #generics #where_clause {
+            #(#variants(#typed_structs)),*
+        }
+
+        #[doc = #state_enum_doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #visibility enum #state_enum_name {
+            #(#variants),*
+        }
+
+        #(#from_impls)*
+
+        impl #impl_generics #any_name #ty_generics #where_clause {
+            #(#downcasts)*
+
+            /// Returns the current state, checked at runtime
+            pub fn state(&self) -> #state_enum_name {
+                match self {
+                    #(Self::#variants(_) => #state_enum_name::#variants),*
+                }
+            }
+
+            /// Returns the names of the current states of the slots
+            pub fn state_names(&self) -> [&'static str; #slot_count] {
+                match self {
+                    #(Self::#variants(_) => #state_names),*
+                }
+            }
+        }
+    }
+}
+
+/// Generates `{StructName}Cell` for `#[type_state(any = true, cell = true)]`: a slot for `Any{StructName}`,
+/// which can be borrowed as the typed object in a state (`{StructName}Guard`), and switched to another state in place.
+///
+/// This is for the objects that live behind `&mut` (e.g. in a long-lived container), so they cannot be moved out for a transition.
+pub fn generate_state_cell(
+    struct_name: &Ident,
+    visibility: &Visibility,
+    generics: &Generics,
+    state_idents: &[Ident],
+    state_bounds: &[&Ident],
+    state_combinations: &[Vec<Ident>],
+) -> TokenStream {
+    let any_name = Ident::new(&format!("Any{}", struct_name), struct_name.span());
+    let state_enum_name = Ident::new(&format!("{}State", struct_name), struct_name.span());
+    let cell_name = Ident::new(&format!("{}Cell", struct_name), struct_name.span());
+    let guard_name = Ident::new(&format!("{}Guard", struct_name), struct_name.span());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let original_args = generic_args(generics);
+    let any_type = quote!(#any_name #ty_generics);
+
+    // the typed object in the states of the guard, and in the next states of `switch()`
+    let typed_struct = quote!(#struct_name<#(#original_args,)* #(#state_idents),*>);
+    let next_idents: Vec<Ident> = state_idents
+        .iter()
+        .map(|ident| Ident::new(&format!("Next{}", ident), ident.span()))
+        .collect();
+    let next_struct = quote!(#struct_name<#(#original_args,)* #(#next_idents),*>);
+
+    // the guard has the lifetime of the borrow, the original generics and the states
+    let mut guard_generics = generics.clone();
+    guard_generics.params.insert(0, syn::parse_quote!('cell));
+    for (state, bound) in state_idents.iter().zip(state_bounds) {
+        guard_generics
+            .params
+            .push(syn::parse_quote!(#state: #bound));
+    }
+    guard_generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(#typed_struct: ::core::convert::Into<#any_type>));
+    let (guard_impl_generics, guard_ty_generics, guard_where_clause) =
+        guard_generics.split_for_impl();
+    let guard_params = guard_generics.params.iter();
+    let guard_predicates = guard_where_clause.map(|clause| &clause.predicates);
+    let next_guard = quote!(#guard_name<'cell, #(#original_args,)* #(#next_idents),*>);
+
+    let try_from_impls: Vec<TokenStream> = state_combinations
+        .iter()
+        .map(|states| {
+            let variant = any_variant_ident(states);
+            let typed_struct = quote!(#struct_name<#(#original_args,)* #(#states),*>);
+            quote! {
+                impl #impl_generics ::core::convert::TryFrom<#any_type> for #typed_struct #where_clause {
+                    type Error = #any_type;
+
+                    #[allow(unreachable_patterns)]
+                    fn try_from(value: #any_type) -> ::core::result::Result<Self, Self::Error> {
+                        match value {
+                            #any_name::#variant(value) => ::core::result::Result::Ok(value),
+                            other => ::core::result::Result::Err(other),
+                        }
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let cell_doc = format!(
+        "Holds `{}` in any of its states, so that it can be switched to another state in place (behind `&mut`)",
+        struct_name
+    );
+    let guard_doc = format!(
+        "`{}` borrowed from `{}` in a known state, it is put back into the cell when the guard is dropped",
+        struct_name, cell_name
+    );
+    let lost = format!(
+        "`{}` was lost by a panic during a transition in `{}`",
+        struct_name, cell_name
+    );
+
+    quote! {
+        #(#try_from_impls)*
+
+        #[doc = #cell_doc]
+        #visibility struct #cell_name #generics #where_clause {
+            // `None` only while a transition is running in a guard, or if it panicked
+            inner: ::core::option::Option<#any_type>,
+        }
+
+        impl #impl_generics #cell_name #ty_generics #where_clause {
+            /// Puts the object into a new cell
+            pub fn new(object: impl ::core::convert::Into<#any_type>) -> Self {
+                Self { inner: ::core::option::Option::Some(object.into()) }
+            }
+
+            /// Returns the type-erased object
+            pub fn get(&self) -> &#any_type {
+                self.inner.as_ref().expect(#lost)
+            }
+
+            /// Returns the type-erased object mutably, e.g. to replace it
+            pub fn get_mut(&mut self) -> &mut #any_type {
+                self.inner.as_mut().expect(#lost)
+            }
+
+            /// Takes the type-erased object out of the cell
+            pub fn into_inner(self) -> #any_type {
+                self.inner.expect(#lost)
+            }
+
+            /// Returns the current state, checked at runtime
+            pub fn state(&self) -> #state_enum_name {
+                self.get().state()
+            }
+
+            /// Borrows the object in the given states, or returns `None` if it is in other states
+            #[allow(clippy::type_complexity)]
+            pub fn in_state<#(#state_idents: #state_bounds),*>(
+                &mut self,
+            ) -> ::core::option::Option<#guard_name<'_, #(#original_args,)* #(#state_idents),*>>
+            where
+                #typed_struct: ::core::convert::TryFrom<#any_type, Error = #any_type>
+                    + ::core::convert::Into<#any_type>,
+            {
+                let object = self.inner.take().expect(#lost);
+                match <#typed_struct as ::core::convert::TryFrom<#any_type>>::try_from(object) {
+                    ::core::result::Result::Ok(value) => ::core::option::Option::Some(#guard_name {
+                        cell: ::core::option::Option::Some(&mut self.inner),
+                        value: ::core::option::Option::Some(value),
+                    }),
+                    ::core::result::Result::Err(object) => {
+                        self.inner = ::core::option::Option::Some(object);
+                        ::core::option::Option::None
+                    }
+                }
+            }
+        }
+
+        #[doc = #guard_doc]
+        #[allow(clippy::type_complexity)]
+        #visibility struct #guard_name<#(#guard_params),*>
+        where
+            #guard_predicates
+        {
+            // both are `Some` until the guard is dropped or switched
+            cell: ::core::option::Option<&'cell mut ::core::option::Option<#any_type>>,
+            value: ::core::option::Option<#typed_struct>,
+        }
+
+        impl #guard_impl_generics #guard_name #guard_ty_generics #guard_where_clause {
+            /// Switches the object to other states with a transition: `guard.switch(|player| player.set_race(Race::Orc))`,
+            /// and returns the guard in the new states
+            #[allow(clippy::type_complexity)]
+            pub fn switch<#(#next_idents: #state_bounds),*>(
+                mut self,
+                transition: impl ::core::ops::FnOnce(#typed_struct) -> #next_struct,
+            ) -> #next_guard
+            where
+                #next_struct: ::core::convert::Into<#any_type>,
+            {
+                let cell = self.cell.take().expect("the guard is not switched yet");
+                let value = self.value.take().expect("the guard is not switched yet");
+                #guard_name {
+                    cell: ::core::option::Option::Some(cell),
+                    value: ::core::option::Option::Some(transition(value)),
+                }
+            }
+        }
+
+        impl #guard_impl_generics ::core::ops::Deref for #guard_name #guard_ty_generics #guard_where_clause {
+            type Target = #typed_struct;
+
+            fn deref(&self) -> &Self::Target {
+                self.value.as_ref().expect("the guard is not switched yet")
+            }
+        }
+
+        impl #guard_impl_generics ::core::ops::DerefMut for #guard_name #guard_ty_generics #guard_where_clause {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                self.value.as_mut().expect("the guard is not switched yet")
+            }
+        }
+
+        impl #guard_impl_generics ::core::ops::Drop for #guard_name #guard_ty_generics #guard_where_clause {
+            fn drop(&mut self) {
+                if let (::core::option::Option::Some(cell), ::core::option::Option::Some(value)) =
+                    (self.cell.take(), self.value.take())
+                {
+                    *cell = ::core::option::Option::Some(value.into());
+                }
+            }
+        }
+    }
+}
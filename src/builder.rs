@@ -0,0 +1,403 @@
+// this file contains the logic of `#[derive(TypeStateBuilder)]`,
+// which generates a type-state builder for a plain struct, by using `#[type_state]` and `#[impl_state]` under the hood:
+// - each required field becomes a flag of the builder, and `build()` requires all of them to be set,
+// - or with `#[builder(sequence = (...))]`, the required fields have to be set in the declared order,
+// - `#[builder(default)]` and `#[builder(optional)]` fields can be set in any state, or skipped.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use stringcase::snake_case;
+use syn::{
+    ext::IdentExt, parenthesized, parse_macro_input, punctuated::Punctuated, Data, DeriveInput,
+    Fields, GenericArgument, Ident, PathArguments, Token, Type,
+};
+
+use crate::{
+    flag_state_ident, generic_args, impl_state_inner, slot_generic_ident, slot_sealer_ident,
+    type_state_inner,
+};
+
+/// How a field is handled by the builder
+enum FieldKind {
+    /// has to be set before `build()`
+    Required,
+    /// `#[builder(default)]`: `Default::default()` if it is not set
+    Default,
+    /// `#[builder(optional)]`: an `Option<T>` field, `None` if it is not set
+    Optional,
+}
+
+struct BuilderField<'a> {
+    name: &'a Ident,
+    ty: &'a Type,
+    kind: FieldKind,
+}
+
+/// The `T` of `Option<T>`
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+pub fn type_state_builder_inner(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match generate_builder(&input) {
+        Ok(output) => output,
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn generate_builder(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let struct_name = &input.ident;
+    let visibility = &input.vis;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let original_args = generic_args(generics);
+
+    let named_fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    struct_name,
+                    "`TypeStateBuilder` expects a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                struct_name,
+                "`TypeStateBuilder` can only be derived for structs",
+            ))
+        }
+    };
+
+    // `#[builder(sequence = (race, level))]`: the required fields have to be set in this order
+    let mut sequence: Option<Vec<Ident>> = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("builder"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("sequence") {
+                let value = meta.value()?;
+                let content;
+                parenthesized!(content in value);
+                let steps = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                sequence = Some(steps.into_iter().collect());
+                Ok(())
+            } else {
+                Err(meta.error("expected `sequence`"))
+            }
+        })?;
+    }
+
+    let mut fields = Vec::new();
+    for field in named_fields {
+        let mut kind = FieldKind::Required;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("builder"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    kind = FieldKind::Default;
+                    Ok(())
+                } else if meta.path.is_ident("optional") {
+                    if option_inner_type(&field.ty).is_none() {
+                        return Err(meta.error("`optional` fields have to be an `Option<T>`"));
+                    }
+                    kind = FieldKind::Optional;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `default` or `optional`"))
+                }
+            })?;
+        }
+
+        fields.push(BuilderField {
+            name: field.ident.as_ref().expect("the fields are named"),
+            ty: &field.ty,
+            kind,
+        });
+    }
+
+    // the flags are prefixed with the struct name: `player_race` -> `PlayerRaceSet`,
+    // so that the builders of different structs don't generate the same marker structs
+    let prefix = snake_case(&struct_name.to_string());
+    let flag = |field: &BuilderField| {
+        Ident::new(
+            &format!("{}_{}", prefix, field.name.unraw()),
+            field.name.span(),
+        )
+    };
+    let mut required: Vec<&BuilderField> = fields
+        .iter()
+        .filter(|field| matches!(field.kind, FieldKind::Required))
+        .collect();
+    if required.is_empty() {
+        return Err(syn::Error::new_spanned(
+            struct_name,
+            "`TypeStateBuilder` needs at least one required field, mark the others with `#[builder(default)]` or `#[builder(optional)]`",
+        ));
+    }
+
+    let builder_name = Ident::new(&format!("{}Builder", struct_name), struct_name.span());
+
+    // the `#[type_state]` arguments, the `#[require]`/`#[switch_to]` arguments of the methods, and the state generics of the builder
+    let (type_state_args, new_states, setter_states, build_states, state_generics) = match &sequence
+    {
+        // a flag for each required field
+        None => {
+            let flags: Vec<Ident> = required.iter().map(|field| flag(field)).collect();
+            let unset_states = flags.iter().map(|flag| flag_state_ident(flag, false));
+            let setter_states: Vec<_> = flags
+                .iter()
+                .map(|flag| {
+                    let unset = flag_state_ident(flag, false);
+                    (quote!(#flag = #unset), quote!(#flag))
+                })
+                .collect();
+            let state_generics: Vec<_> = flags
+                .iter()
+                .enumerate()
+                .map(|(i, flag)| {
+                    let flag = flag.to_string();
+                    (
+                        slot_generic_ident(&builder_name, i, Some(&flag)),
+                        slot_sealer_ident(&builder_name, i, Some(&flag)),
+                    )
+                })
+                .collect();
+            (
+                quote!(flags = (#(#flags),*)),
+                quote!(#(#flags = #unset_states),*),
+                setter_states,
+                quote!(#(#flags),*),
+                state_generics,
+            )
+        }
+        // a single slot, which goes through the steps: `PlayerInitial -> PlayerRaceSet -> PlayerLevelSet`
+        Some(steps) => {
+            for step in steps {
+                if !required.iter().any(|field| field.name == step) {
+                    return Err(syn::Error::new_spanned(
+                        step,
+                        format!("`{}` is not a required field of `{}`", step, struct_name),
+                    ));
+                }
+                if steps.iter().filter(|other| *other == step).count() > 1 {
+                    return Err(syn::Error::new_spanned(
+                        step,
+                        format!("`{}` is listed more than once in `sequence`", step),
+                    ));
+                }
+            }
+            // the setters are generated in the order of the sequence
+            let mut ordered = Vec::new();
+            for step in steps {
+                ordered.extend(required.iter().find(|field| field.name == step).copied());
+            }
+            if let Some(missing) = required
+                .iter()
+                .find(|field| !steps.iter().any(|step| field.name == step))
+            {
+                return Err(syn::Error::new_spanned(
+                    missing.name,
+                    format!(
+                        "`{}` is a required field, it has to be listed in `sequence`",
+                        missing.name
+                    ),
+                ));
+            }
+            required = ordered;
+
+            let initial = Ident::new(&format!("{}Initial", struct_name), struct_name.span());
+            let states: Vec<Ident> = std::iter::once(initial.clone())
+                .chain(
+                    required
+                        .iter()
+                        .map(|field| flag_state_ident(&flag(field), true)),
+                )
+                .collect();
+            let transitions = states.windows(2).map(|pair| {
+                let (from, to) = (&pair[0], &pair[1]);
+                quote!(#from -> #to)
+            });
+            let setter_states: Vec<_> = states
+                .windows(2)
+                .map(|pair| {
+                    let (from, to) = (&pair[0], &pair[1]);
+                    (quote!(#from), quote!(#to))
+                })
+                .collect();
+            let last = states.last().expect("there is at least one required field");
+            (
+                quote! {
+                    states = (#(#states),*),
+                    slots = (#initial),
+                    transitions = (#(#transitions),*)
+                },
+                quote!(#initial),
+                setter_states,
+                quote!(#last),
+                vec![(
+                    slot_generic_ident(&builder_name, 0, None),
+                    Ident::new(&format!("Sealer{}", builder_name), builder_name.span()),
+                )],
+            )
+        }
+    };
+    let field_names: Vec<&Ident> = fields.iter().map(|field| field.name).collect();
+
+    // the optional fields are already `Option<T>`, the others are wrapped
+    let builder_fields = fields.iter().map(|field| {
+        let name = field.name;
+        let ty = field.ty;
+        match field.kind {
+            FieldKind::Optional => quote!(#name: #ty),
+            _ => quote!(#name: ::core::option::Option<#ty>),
+        }
+    });
+
+    let builder_struct = quote! {
+        #visibility struct #builder_name #generics #where_clause {
+            #(#builder_fields,)*
+        }
+    };
+    let builder_struct = type_state_inner(type_state_args.into(), builder_struct.into());
+
+    // `PlayerBuilder { race: Some(race), level: self.level, ... }`
+    let builder_type = quote!(#builder_name<#(#original_args),*>);
+    let required_setters = required
+        .iter()
+        .zip(&setter_states)
+        .map(|(field, (from, to))| {
+            let name = field.name;
+            let ty = field.ty;
+            let other_fields = field_names.iter().filter(|other| *other != &name);
+            let doc = format!("Sets `{}`", name);
+            quote! {
+                #[doc = #doc]
+                #[require(#from)]
+                #[switch_to(#to)]
+                #visibility fn #name(self, #name: #ty) -> #builder_type {
+                    #builder_name {
+                        #name: ::core::option::Option::Some(#name),
+                        #(#other_fields: self.#other_fields),*
+                    }
+                }
+            }
+        });
+
+    let build_fields = fields.iter().map(|field| {
+        let name = field.name;
+        match field.kind {
+            FieldKind::Required => {
+                quote!(#name: self.#name.expect("type safety ensures this is set"))
+            }
+            FieldKind::Default => quote!(#name: self.#name.unwrap_or_default()),
+            FieldKind::Optional => quote!(#name: self.#name),
+        }
+    });
+    let new_doc = format!("Creates `{}` with none of the fields set", builder_name);
+    let build_doc = format!(
+        "Builds `{}`, available once all of the required fields are set",
+        struct_name
+    );
+
+    let builder_impl = quote! {
+        impl #impl_generics #builder_type #where_clause {
+            #[doc = #new_doc]
+            #[require(#new_states)]
+            #visibility fn new() -> #builder_type {
+                #builder_name {
+                    #(#field_names: ::core::option::Option::None),*
+                }
+            }
+
+            #(#required_setters)*
+
+            #[doc = #build_doc]
+            #[require(#build_states)]
+            #visibility fn build(self) -> #struct_name #ty_generics {
+                #struct_name {
+                    #(#build_fields),*
+                }
+            }
+        }
+    };
+    let builder_impl = impl_state_inner(TokenStream::new(), builder_impl.into());
+
+    // the default and optional fields can be set in any state, so they don't need `#[impl_state]`
+    let mut any_state_generics = generics.clone();
+    for (state, bound) in &state_generics {
+        any_state_generics
+            .params
+            .push(syn::parse_quote!(#state: #bound));
+    }
+    let (state_impl_generics, state_ty_generics, state_where_clause) =
+        any_state_generics.split_for_impl();
+
+    let other_setters = fields.iter().filter_map(|field| {
+        let name = field.name;
+        let (ty, doc) = match field.kind {
+            FieldKind::Required => return None,
+            FieldKind::Default => (
+                field.ty,
+                format!("Sets `{}`, or it will be `Default::default()`", name),
+            ),
+            FieldKind::Optional => (
+                option_inner_type(field.ty).expect("checked above"),
+                format!("Sets `{}`, or it will be `None`", name),
+            ),
+        };
+        Some(quote! {
+            #[doc = #doc]
+            #visibility fn #name(mut self, #name: #ty) -> Self {
+                self.#name = ::core::option::Option::Some(#name);
+                self
+            }
+        })
+    });
+
+    let builder_doc = format!(
+        "Returns `{}`, the type-state builder of `{}`",
+        builder_name, struct_name
+    );
+    let builder_struct = TokenStream2::from(builder_struct);
+    let builder_impl = TokenStream2::from(builder_impl);
+
+    let output = quote! {
+        #builder_struct
+
+        #builder_impl
+
+        impl #state_impl_generics #builder_name #state_ty_generics #state_where_clause {
+            #(#other_setters)*
+        }
+
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            #[doc = #builder_doc]
+            #visibility fn builder() -> #builder_type {
+                #builder_name::new()
+            }
+        }
+    };
+
+    Ok(output.into())
+}
@@ -0,0 +1,218 @@
+// this file contains the logic that exports the state machine of a struct as a diagram:
+// the states of each slot, and the transitions between them (the `#[require]`/`#[switch_to]` pairs of the methods).
+//
+// The graph covers the methods of one `#[impl_state]` block, the description of the struct comes from `#[type_state]`.
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{info::TypeStateInfo, model::to_json};
+
+/// The environment variable that makes `#[impl_state]` write the diagrams into a directory
+pub const GRAPH_DIR_ENV: &str = "STATE_SHIFT_GRAPH_DIR";
+
+/// The state graph of a struct
+pub struct StateGraph {
+    pub struct_name: String,
+    pub slots: Vec<SlotGraph>,
+}
+
+/// The states of a slot, and the transitions between them
+pub struct SlotGraph {
+    /// The name of the slot, or `slot 1`, `slot 2`... for the positional slots
+    pub label: String,
+    pub default: String,
+    pub states: Vec<String>,
+    pub edges: Vec<Edge>,
+}
+
+/// A transition of a slot, by a method
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub method: String,
+}
+
+pub fn state_graph(struct_name: &str, info: &TypeStateInfo) -> StateGraph {
+    let slots = info
+        .slots
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            let label = slot
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("slot {}", i + 1));
+
+            // a transition from a generic state (`#[require(A)]`) is a transition from any of the states of the slot,
+            // and a transition into a generic state is skipped, since its target is not known
+            // `(from, to, method)`, `from` is `None` for a generic state
+            let mut edges: Vec<(Option<&String>, &String, &String)> = Vec::new();
+            let mut used_states: Vec<&str> = vec![&slot.default];
+            for method in &info.methods {
+                let is_generic = |state: &String| method.generics.contains(state);
+                let required = &method.required[i];
+                if !is_generic(required) {
+                    used_states.push(required);
+                }
+                let Some(switched_to) = &method.switched_to else {
+                    continue;
+                };
+                let to = &switched_to[i];
+                if to == required || is_generic(to) {
+                    continue;
+                }
+                used_states.push(to);
+                let from = (!is_generic(required)).then_some(required);
+                edges.push((from, to, &method.name));
+            }
+
+            // the states listed for the slot, or else the states that are used in the slot, in the declared order
+            let states: Vec<String> = match &slot.states {
+                Some(states) => states.clone(),
+                None => info
+                    .states
+                    .iter()
+                    .filter(|state| used_states.contains(&state.as_str()))
+                    .cloned()
+                    .collect(),
+            };
+
+            let edges = edges
+                .into_iter()
+                .flat_map(|(from, to, method)| {
+                    let sources: Vec<&String> = match from {
+                        Some(from) => vec![from],
+                        None => states.iter().filter(|state| *state != to).collect(),
+                    };
+                    sources.into_iter().map(|from| Edge {
+                        from: from.clone(),
+                        to: to.clone(),
+                        method: method.clone(),
+                    })
+                })
+                .collect();
+
+            SlotGraph {
+                label,
+                default: slot.default.clone(),
+                states,
+                edges,
+            }
+        })
+        .collect();
+
+    StateGraph {
+        struct_name: struct_name.to_string(),
+        slots,
+    }
+}
+
+/// The Graphviz DOT diagram: a cluster for each slot, the methods are the labels of the edges
+pub fn to_dot(graph: &StateGraph) -> String {
+    let mut dot = String::new();
+    writeln!(dot, "digraph {} {{", graph.struct_name).unwrap();
+    writeln!(dot, "    label = \"{}\";", graph.struct_name).unwrap();
+    for (i, slot) in graph.slots.iter().enumerate() {
+        writeln!(dot, "    subgraph cluster_{} {{", i).unwrap();
+        writeln!(dot, "        label = \"{}\";", slot.label).unwrap();
+        // the start of the slot points to its default state
+        writeln!(dot, "        \"{}:start\" [shape = point];", i).unwrap();
+        for state in &slot.states {
+            writeln!(dot, "        \"{}:{}\" [label = \"{}\"];", i, state, state).unwrap();
+        }
+        writeln!(
+            dot,
+            "        \"{}:start\" -> \"{}:{}\";",
+            i, i, slot.default
+        )
+        .unwrap();
+        for edge in &slot.edges {
+            writeln!(
+                dot,
+                "        \"{}:{}\" -> \"{}:{}\" [label = \"{}\"];",
+                i, edge.from, i, edge.to, edge.method
+            )
+            .unwrap();
+        }
+        writeln!(dot, "    }}").unwrap();
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+/// The Mermaid `stateDiagram-v2`: a composite state for each slot if there are multiple slots,
+/// the methods are the labels of the transitions
+pub fn to_mermaid(graph: &StateGraph) -> String {
+    let mut mermaid = String::new();
+    writeln!(mermaid, "stateDiagram-v2").unwrap();
+
+    if let [slot] = &graph.slots[..] {
+        for state in &slot.states {
+            writeln!(mermaid, "    {}", state).unwrap();
+        }
+        writeln!(mermaid, "    [*] --> {}", slot.default).unwrap();
+        for edge in &slot.edges {
+            writeln!(
+                mermaid,
+                "    {} --> {}: {}",
+                edge.from, edge.to, edge.method
+            )
+            .unwrap();
+        }
+        return mermaid;
+    }
+
+    // the same state can be in multiple slots, so the states are prefixed with their slot
+    for (i, slot) in graph.slots.iter().enumerate() {
+        writeln!(mermaid, "    state \"{}\" as slot{} {{", slot.label, i).unwrap();
+        for state in &slot.states {
+            writeln!(
+                mermaid,
+                "        state \"{}\" as slot{}_{}",
+                state, i, state
+            )
+            .unwrap();
+        }
+        writeln!(mermaid, "        [*] --> slot{}_{}", i, slot.default).unwrap();
+        for edge in &slot.edges {
+            writeln!(
+                mermaid,
+                "        slot{}_{} --> slot{}_{}: {}",
+                i, edge.from, i, edge.to, edge.method
+            )
+            .unwrap();
+        }
+        writeln!(mermaid, "    }}").unwrap();
+    }
+    mermaid
+}
+
+/// Writes the diagrams and the JSON model of the struct into `STATE_SHIFT_GRAPH_DIR`, if it is set:
+/// `{StructName}.dot`, `{StructName}.mmd` and `{StructName}.json`
+///
+/// Returns the message of the error, if a file cannot be written.
+pub fn write_graphs(graph: &StateGraph, info: &TypeStateInfo) -> Result<(), String> {
+    let Some(dir) = std::env::var_os(GRAPH_DIR_ENV) else {
+        return Ok(());
+    };
+    let dir = Path::new(&dir);
+
+    let files = [
+        ("dot", to_dot(graph)),
+        ("mmd", to_mermaid(graph)),
+        ("json", to_json(&graph.struct_name, info)),
+    ];
+    for (extension, diagram) in files {
+        let path = dir.join(format!("{}.{}", graph.struct_name, extension));
+        if let Err(error) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, diagram)) {
+            return Err(format!(
+                "`{}`: cannot write the state graph of `{}` into `{}`: {}",
+                GRAPH_DIR_ENV,
+                graph.struct_name,
+                path.display(),
+                error
+            ));
+        }
+    }
+
+    Ok(())
+}
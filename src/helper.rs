@@ -1,5 +1,35 @@
-use proc_macro::TokenTree;
-use syn::{punctuated::Punctuated, Attribute, Ident, Token};
+use proc_macro2::TokenStream;
+use quote::quote;
+use stringcase::pascal_case;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Attribute, GenericParam, Generics, Ident, Token,
+};
+
+use crate::info::TypeStateInfo;
+
+/// A single argument of `#[require]` or `#[switch_to]` macros
+#[derive(Clone)]
+pub enum StateArg {
+    /// `#[require(State1, State2)]`
+    Positional(Ident),
+    /// `#[require(slot_name = State1)]`
+    Named { slot: Ident, state: Ident },
+}
+
+impl Parse for StateArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            let state: Ident = input.parse()?;
+            Ok(StateArg::Named { slot: ident, state })
+        } else {
+            Ok(StateArg::Positional(ident))
+        }
+    }
+}
 
 /// Helper function to find and remove an attribute by name
 fn find_and_remove_attr(attrs: &mut Vec<Attribute>, attr_name: &str) -> Option<Attribute> {
@@ -9,34 +39,318 @@ fn find_and_remove_attr(attrs: &mut Vec<Attribute>, attr_name: &str) -> Option<A
     Some(attrs.remove(pos))
 }
 
-/// Extracts the arguments from a macro call
+/// Extracts the arguments from a macro call, `None` if the macro is not there
 pub fn extract_macro_args(
     attrs: &mut Vec<Attribute>,
     macro_name: &str,
-) -> Option<Punctuated<Ident, Token![,]>> {
-    let attr = find_and_remove_attr(attrs, macro_name)?;
-    let args: Punctuated<Ident, Token![,]> =
-        attr.parse_args_with(Punctuated::parse_terminated).ok()?;
-    Some(args)
+) -> syn::Result<Option<Punctuated<StateArg, Token![,]>>> {
+    let Some(attr) = find_and_remove_attr(attrs, macro_name) else {
+        return Ok(None);
+    };
+    attr.parse_args_with(Punctuated::parse_terminated).map(Some)
 }
 
 pub fn is_single_letter(ident: &Ident) -> bool {
     ident.to_string().len() == 1
 }
 
-pub fn extract_idents_from_group(token: &TokenTree, error_msg: &str) -> Vec<Ident> {
-    match token {
-        proc_macro::TokenTree::Group(group) => group
-            .stream()
-            .into_iter()
-            .filter_map(|tt| {
-                if let proc_macro::TokenTree::Ident(ident) = tt {
-                    Some(Ident::new(&format!("{}", ident), ident.span().into()))
-                } else {
-                    None
+/// The generics of a struct as arguments: `<'a, T: Debug, const N: usize>` -> `'a, T, N`
+pub fn generic_args(generics: &Generics) -> Vec<TokenStream> {
+    generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(lifetime) => {
+                let lifetime = &lifetime.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(constant) => {
+                let ident = &constant.ident;
+                quote!(#ident)
+            }
+        })
+        .collect()
+}
+
+/// The generic used for a state slot: `PlayerBuilderState1` or `PlayerBuilderStateRace` for a named slot
+pub fn slot_generic_ident(
+    struct_name: &Ident,
+    slot_index: usize,
+    slot_name: Option<&str>,
+) -> Ident {
+    let suffix = match slot_name {
+        Some(name) => pascal_case(name),
+        None => (slot_index + 1).to_string(),
+    };
+    Ident::new(
+        &format!("{}State{}", struct_name, suffix),
+        struct_name.span(),
+    )
+}
+
+/// The states generated for a flag: `RaceSet` and `RaceUnset` for the flag `race`
+pub fn flag_state_ident(flag: &Ident, is_set: bool) -> Ident {
+    let suffix = if is_set { "Set" } else { "Unset" };
+    Ident::new(
+        &format!("{}{}", pascal_case(&flag.to_string()), suffix),
+        flag.span(),
+    )
+}
+
+/// The sealing trait of a slot that lists its own states: `SealerPlayerBuilderSlot_1` or `SealerPlayerBuilderSlot_race` for a named slot
+///
+/// The underscore keeps it apart from the sealer of another struct, `SealerDoor1` of `Door1` for example.
+pub fn slot_sealer_ident(struct_name: &Ident, slot_index: usize, slot_name: Option<&str>) -> Ident {
+    let suffix = match slot_name {
+        Some(name) => name.to_string(),
+        None => (slot_index + 1).to_string(),
+    };
+    Ident::new(
+        &format!("Sealer{}Slot_{}", struct_name, suffix),
+        struct_name.span(),
+    )
+}
+
+/// The trait that allows a single `#[impl_state]` block for the struct, if one of them needs all of the methods:
+/// `SingleImplStateBlockPlayerBuilder`
+pub fn single_block_ident(struct_name: &Ident) -> Ident {
+    Ident::new(
+        &format!("SingleImplStateBlock{}", struct_name),
+        struct_name.span(),
+    )
+}
+
+/// The guard in `_state` of a struct with terminal states: `DropCheckTransaction`
+pub fn drop_check_ident(struct_name: &Ident) -> Ident {
+    Ident::new(&format!("DropCheck{}", struct_name), struct_name.span())
+}
+
+/// Converts the arguments of `#[require]` or `#[switch_to]` into the positional form: `(State1, B, State3)`
+///
+/// Flags (`race`) are converted into named arguments (`race = RaceSet`).
+/// Named arguments (`slot = State`) are placed into their slot's position, and the slots that are not mentioned:
+/// - are filled with `unmentioned[i]` if it is provided (`#[switch_to]` keeps the required states),
+/// - or else become generics (`#[require]` accepts any state for them).
+///
+/// Returns the positional arguments, and the generics among them.
+/// The positional form must list a state for each slot.
+/// The errors are reported on the argument that causes them.
+pub fn resolve_state_args(
+    args: &Punctuated<StateArg, Token![,]>,
+    unmentioned: Option<&[Ident]>,
+    struct_name: &Ident,
+    info: &TypeStateInfo,
+    fn_name: &Ident,
+) -> syn::Result<(Punctuated<Ident, Token![,]>, Vec<Ident>)> {
+    // for flags, `#[require(race)]` is a shorthand for `#[require(race = RaceSet)]`
+    let flags: Vec<&str> = info
+        .slots
+        .iter()
+        .filter(|slot| slot.is_flag)
+        .filter_map(|slot| slot.name.as_deref())
+        .collect();
+    let args: Punctuated<StateArg, Token![,]> = args
+        .iter()
+        .map(|arg| match arg {
+            StateArg::Positional(flag) if flags.iter().any(|name| flag == name) => {
+                StateArg::Named {
+                    slot: flag.clone(),
+                    state: flag_state_ident(flag, true),
                 }
+            }
+            _ => arg.clone(),
+        })
+        .collect();
+    let args = &args;
+
+    let is_named = |arg: &StateArg| matches!(arg, StateArg::Named { .. });
+
+    // positional arguments are used as they are, single letters are the generics
+    if !args.iter().any(is_named) {
+        let positional: Punctuated<Ident, Token![,]> = args
+            .iter()
+            .map(|arg| match arg {
+                StateArg::Positional(state) => state.clone(),
+                StateArg::Named { .. } => unreachable!(),
             })
-            .collect(),
-        _ => panic!("{}", error_msg),
+            .collect();
+        // the other checks and the state graph rely on a state for each slot
+        if positional.len() != info.slots.len() {
+            let plural = |count: usize| if count == 1 { "" } else { "s" };
+            return Err(syn::Error::new(
+                fn_name.span(),
+                format!(
+                    "`{}` lists {} state{} but `{}` has {} slot{}.",
+                    fn_name,
+                    positional.len(),
+                    plural(positional.len()),
+                    struct_name,
+                    info.slots.len(),
+                    plural(info.slots.len())
+                ),
+            ));
+        }
+        let generics = positional
+            .iter()
+            .filter(|ident| is_single_letter(ident))
+            .cloned()
+            .collect();
+        return Ok((positional, generics));
+    }
+
+    if let Some(StateArg::Positional(positional)) = args.iter().find(|arg| !is_named(arg)) {
+        return Err(syn::Error::new(
+            positional.span(),
+            format!(
+                "Function `{}`: cannot mix named (`slot = State`) and positional arguments.",
+                fn_name
+            ),
+        ));
+    }
+
+    let mut slot_names: Vec<&str> = Vec::new();
+    for slot in &info.slots {
+        let Some(name) = slot.name.as_deref() else {
+            return Err(syn::Error::new(
+                fn_name.span(),
+                format!(
+                    "Function `{}`: named arguments require the slots of `{}` to be named, e.g. `slots = (race: Initial)`.",
+                    fn_name, struct_name
+                ),
+            ));
+        };
+        slot_names.push(name);
+    }
+
+    let mut states: Vec<Option<Ident>> = vec![None; slot_names.len()];
+    for arg in args {
+        if let StateArg::Named { slot, state } = arg {
+            let Some(index) = slot_names.iter().position(|name| slot == name) else {
+                return Err(syn::Error::new(
+                    slot.span(),
+                    format!(
+                        "Function `{}`: `{}` has no slot named `{}`.",
+                        fn_name, struct_name, slot
+                    ),
+                ));
+            };
+            if states[index].replace(state.clone()).is_some() {
+                return Err(syn::Error::new(
+                    slot.span(),
+                    format!(
+                        "Function `{}`: slot `{}` is mentioned more than once.",
+                        fn_name, slot
+                    ),
+                ));
+            }
+        }
+    }
+
+    let mut generics = Vec::new();
+    let positional = states
+        .into_iter()
+        .enumerate()
+        .map(|(i, state)| match (state, unmentioned) {
+            (Some(state), _) => {
+                if is_single_letter(&state) {
+                    generics.push(state.clone());
+                }
+                state
+            }
+            (None, Some(unmentioned)) => unmentioned[i].clone(),
+            (None, None) => {
+                let generic = slot_generic_ident(struct_name, i, Some(slot_names[i]));
+                generics.push(generic.clone());
+                generic
+            }
+        })
+        .collect();
+
+    Ok((positional, generics))
+}
+
+/// Rejects the states that are not listed for their slot: `slots = (race: Initial in (Initial, RaceSet))`
+///
+/// The compiler would reject them anyway due to the slot's sealer trait,
+/// this is only for a clearer error message.
+pub fn check_slot_states(
+    args: &Punctuated<Ident, Token![,]>,
+    generics: &[Ident],
+    info: &TypeStateInfo,
+    fn_name: &Ident,
+) -> syn::Result<()> {
+    for (i, (state, slot)) in args.iter().zip(&info.slots).enumerate() {
+        let Some(slot_states) = &slot.states else {
+            continue;
+        };
+
+        if !generics.contains(state) && !slot_states.iter().any(|slot_state| state == slot_state) {
+            let slot_name = slot.name.clone().unwrap_or_else(|| (i + 1).to_string());
+            return Err(syn::Error::new(
+                state.span(),
+                format!(
+                    "Function `{}`: `{}` is not one of the states of slot `{}`, expected one of: {}.",
+                    fn_name,
+                    state,
+                    slot_name,
+                    slot_states.join(", ")
+                ),
+            ));
+        }
     }
+
+    Ok(())
+}
+
+/// Rejects the state changes that are not declared in `transitions = (Initial -> RaceSet, ...)`
+///
+/// Each slot that changes its state is checked separately.
+/// If the slot was in any state (a generic), only `_ -> State` transitions are accepted.
+pub fn check_transitions(
+    required: &Punctuated<Ident, Token![,]>,
+    switched: &Punctuated<Ident, Token![,]>,
+    generics: &[Ident],
+    info: &TypeStateInfo,
+    struct_name: &Ident,
+    fn_name: &Ident,
+) -> syn::Result<()> {
+    let Some(transitions) = &info.transitions else {
+        return Ok(());
+    };
+
+    for (from, to) in required.iter().zip(switched) {
+        if from == to {
+            continue;
+        }
+
+        let from_any = generics.contains(from);
+        let is_declared = transitions.iter().any(|transition| {
+            let from_matches = match &transition.from {
+                Some(transition_from) => !from_any && from == transition_from,
+                None => true,
+            };
+            from_matches && to == &transition.to
+        });
+
+        if !is_declared {
+            let from = if from_any {
+                "_".to_string()
+            } else {
+                from.to_string()
+            };
+            return Err(syn::Error::new(
+                to.span(),
+                format!(
+                    "Function `{}`: transition `{} -> {}` is not declared in the `transitions` of `{}`.",
+                    fn_name, from, to, struct_name
+                ),
+            ));
+        }
+    }
+
+    Ok(())
 }
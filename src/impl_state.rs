@@ -1,12 +1,188 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ImplItem, ItemImpl, Type};
+use quote::{quote, quote_spanned};
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    FnArg, Generics, Ident, ImplItem, ImplItemFn, ItemImpl, LitBool, PathArguments, Signature,
+    Token, Type, Visibility,
+};
 
-use crate::{extract_macro_args, generate_impl_block_for_method_based_on_require_args};
+use crate::{
+    check_slot_states, check_transitions, extract_macro_args,
+    generate_impl_block_for_method_based_on_require_args,
+    graph::{state_graph, to_dot, to_mermaid, write_graphs},
+    helper::StateArg,
+    info::{MethodInfo, TypeStateInfo},
+    model::{to_json, visibility_string},
+    reachability::{analyze, report, Reachability},
+    resolve_state_args,
+    runtime::generate_runtime,
+    single_block_ident,
+};
 
-pub fn impl_state_inner(item: TokenStream) -> TokenStream {
-    // Parse the impl block
-    let mut input = parse_macro_input!(item as ItemImpl);
+/// Arguments of the `#[impl_state]` macro, all of them are optional
+#[derive(Default)]
+struct ImplStateArgs {
+    /// `runtime = true`: generate the runtime mirror of the transitions
+    runtime: bool,
+    /// `dot = true`: generate the `STATE_GRAPH_DOT` constant
+    dot: bool,
+    /// `mermaid = true`: generate the `STATE_GRAPH_MERMAID` constant, and show the diagram in the docs
+    mermaid: bool,
+    /// `json = true`: generate the `STATE_MODEL_JSON` constant
+    json: bool,
+    /// `reachability = warn` or `reachability = deny`: check the state graph for the unreachable and the dead-end states
+    reachability: Option<Reachability>,
+}
+
+impl Parse for ImplStateArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = ImplStateArgs::default();
+
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            match key.to_string().as_str() {
+                "runtime" => args.runtime = input.parse::<LitBool>()?.value,
+                "dot" => args.dot = input.parse::<LitBool>()?.value,
+                "mermaid" => args.mermaid = input.parse::<LitBool>()?.value,
+                "json" => args.json = input.parse::<LitBool>()?.value,
+                "reachability" => {
+                    let level: Ident = input.parse()?;
+                    args.reachability = match level.to_string().as_str() {
+                        "warn" => Some(Reachability::Warn),
+                        "deny" => Some(Reachability::Deny),
+                        _ => {
+                            return Err(syn::Error::new(level.span(), "expected `warn` or `deny`"))
+                        }
+                    };
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected `runtime`, `dot`, `mermaid`, `json` or `reachability`",
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// The states of a method, extracted from its `#[require]` and `#[switch_to]` macros
+pub struct MethodTransition {
+    /// The signature of the method, before it is modified
+    pub sig: Signature,
+    /// The positional arguments of `#[require]`: `(State1, B, C)`
+    pub required: Punctuated<Ident, Token![,]>,
+    /// The positional arguments of `#[switch_to]`, `None` if the method doesn't have it
+    pub switched_to: Option<Punctuated<Ident, Token![,]>>,
+    /// The generics among the required states: `B, C`
+    pub generics: Vec<Ident>,
+    /// The visibility of the method
+    pub visibility: Visibility,
+    /// `#[switch_to(..., on_err = keep)]`: the object in the error of a `Result` keeps the required states
+    pub keep_on_err: bool,
+}
+
+/// Removes `on_err = keep` from the arguments of `#[switch_to]`, so that they only contain the states
+fn take_on_err(
+    args: Punctuated<StateArg, Token![,]>,
+    keep_on_err: &mut bool,
+    fn_name: &Ident,
+) -> syn::Result<Punctuated<StateArg, Token![,]>> {
+    let mut states = Punctuated::new();
+    for arg in args {
+        match arg {
+            StateArg::Named { slot, state } if slot == "on_err" => {
+                if state != "keep" {
+                    return Err(syn::Error::new(
+                        state.span(),
+                        format!(
+                            "Function `{}`: expected `on_err = keep`, found `on_err = {}`.",
+                            fn_name, state
+                        ),
+                    ));
+                }
+                *keep_on_err = true;
+            }
+            _ => states.push(arg),
+        }
+    }
+    Ok(states)
+}
+
+/// The input of `__impl_state_with_info!`: `{ <the description of the struct> } (<the arguments of #[impl_state]>) impl ... { ... }`
+struct ImplStateInput {
+    info: TypeStateInfo,
+    args: ImplStateArgs,
+    item: ItemImpl,
+}
+
+impl Parse for ImplStateInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let info;
+        braced!(info in input);
+        let args;
+        parenthesized!(args in input);
+        Ok(ImplStateInput {
+            info: info.parse()?,
+            args: args.parse()?,
+            item: input.parse()?,
+        })
+    }
+}
+
+/// `#[impl_state]` needs the description of the struct from `#[type_state]`,
+/// so it expands into a call of the macro that `#[type_state]` generates with the name of the struct:
+/// `PlayerBuilder! { (runtime = true) impl PlayerBuilder { ... } }`, which calls `impl_state_with_info_inner` with the description
+pub fn impl_state_inner(args: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemImpl);
+    let args = proc_macro2::TokenStream::from(args);
+
+    // the path of the struct, without the generics: `crate::player::PlayerBuilder`
+    let mut path = match *input.self_ty {
+        Type::Path(ref type_path) if type_path.qself.is_none() => type_path.path.clone(),
+        _ => {
+            return syn::Error::new_spanned(&input.self_ty, "Unsupported type for impl block")
+                .to_compile_error()
+                .into()
+        }
+    };
+    path.segments.last_mut().unwrap().arguments = PathArguments::None;
+
+    quote! {
+        #path! { (#args) #input }
+    }
+    .into()
+}
+
+pub fn impl_state_with_info_inner(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ImplStateInput);
+
+    // This macro is called from the macro of the struct, so the errors are spanned on the tokens of the `impl` block,
+    // otherwise they would be reported on `#[type_state]`
+    match expand_impl_state(input) {
+        Ok(expanded) => expanded.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand_impl_state(input: ImplStateInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ImplStateInput {
+        info: mut type_state_info,
+        args,
+        item: mut input,
+    } = input;
 
     // Extract the type name and generics of the struct being implemented
     let (struct_name, struct_generics) = match *input.self_ty {
@@ -16,28 +192,34 @@ pub fn impl_state_inner(item: TokenStream) -> TokenStream {
             let struct_generics = &last_segment.arguments;
             (struct_name, struct_generics)
         }
-        _ => panic!("Unsupported type for impl block"),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.self_ty,
+                "Unsupported type for impl block",
+            ))
+        }
     };
 
     // Extract the methods from the impl block
     let mut methods = Vec::new();
+    let mut transitions = Vec::new();
 
     for item in input.items.iter_mut() {
         if let ImplItem::Fn(ref mut method) = item {
-            // Extract `#[require]` arguments if they exist
-            let require_args = extract_macro_args(&mut method.attrs, "require");
-
-            // Generate the impl block for the method based on the extracted #[require] arguments
-            let modified_method = if let Some(require_args) = require_args {
-                generate_impl_block_for_method_based_on_require_args(
-                    method,
-                    &struct_name,
-                    &require_args,
-                    &input.generics,
-                    struct_generics,
-                )
-            } else {
-                quote! { #method }
+            // a method with an error is replaced by the error, so that the other methods are still generated
+            let modified_method = match expand_method(
+                method,
+                &struct_name,
+                struct_generics,
+                &input.generics,
+                &type_state_info,
+            ) {
+                Ok(Some((modified_method, transition))) => {
+                    transitions.push(transition);
+                    modified_method
+                }
+                Ok(None) => quote! { #method },
+                Err(error) => error.to_compile_error(),
             };
 
             // Push the modified method to the list of methods
@@ -45,10 +227,242 @@ pub fn impl_state_inner(item: TokenStream) -> TokenStream {
         }
     }
 
+    // Generate the runtime mirror of the transitions, if requested
+    let runtime = if args.runtime {
+        generate_runtime(
+            &struct_name,
+            struct_generics,
+            &input.generics,
+            &transitions,
+            &type_state_info,
+        )?
+    } else {
+        quote! {}
+    };
+
+    // The methods of this block, for the state graph
+    type_state_info.methods = transitions
+        .iter()
+        .map(|transition| MethodInfo {
+            name: transition.sig.ident.to_string(),
+            required: transition
+                .required
+                .iter()
+                .map(|state| state.to_string())
+                .collect(),
+            switched_to: transition
+                .switched_to
+                .as_ref()
+                .map(|states| states.iter().map(|state| state.to_string()).collect()),
+            generics: transition
+                .generics
+                .iter()
+                .map(|generic| generic.to_string())
+                .collect(),
+            visibility: visibility_string(&transition.visibility),
+            is_constructor: !matches!(transition.sig.inputs.first(), Some(FnArg::Receiver(_))),
+        })
+        .collect();
+
+    // the graph constants need all of the methods of the struct,
+    // and the graph is only built for the blocks that export or check it
+    let exports_graph = args.dot || args.mermaid || args.json;
+    let graph = (exports_graph || args.reachability.is_some())
+        .then(|| state_graph(&struct_name.to_string(), &type_state_info));
+
+    // Generate the state graph constants (and the diagram in the docs for Mermaid), if requested
+    let graph_consts = match &graph {
+        Some(graph) if exports_graph => {
+            write_graphs(graph, &type_state_info)
+                .map_err(|message| syn::Error::new(input.self_ty.span(), message))?;
+
+            let mut impl_docs = quote! {};
+            let mut items = quote! {};
+            if args.dot {
+                let dot = to_dot(graph);
+                let doc = format!(
+                    "The states and the transitions of `{}` as a Graphviz DOT diagram",
+                    struct_name
+                );
+                items.extend(quote! {
+                    #[doc = #doc]
+                    pub const STATE_GRAPH_DOT: &'static str = #dot;
+                });
+            }
+            if args.mermaid {
+                let mermaid = to_mermaid(graph);
+                let doc = format!(
+                    "The states and the transitions of `{}` as a Mermaid `stateDiagram-v2`",
+                    struct_name
+                );
+                // the diagram is also shown in the docs of this `impl` block
+                let diagram_doc = format!(
+                    "The lifecycle of `{}`:\n\n```mermaid\n{}```",
+                    struct_name, mermaid
+                );
+                impl_docs = quote! { #[doc = #diagram_doc] };
+                items.extend(quote! {
+                    #[doc = #doc]
+                    pub const STATE_GRAPH_MERMAID: &'static str = #mermaid;
+                });
+            }
+            if args.json {
+                let json = to_json(&struct_name.to_string(), &type_state_info);
+                let doc = format!("The typestate model of `{}` as JSON", struct_name);
+                items.extend(quote! {
+                    #[doc = #doc]
+                    pub const STATE_MODEL_JSON: &'static str = #json;
+                });
+            }
+
+            generate_default_state_impl(
+                &struct_name,
+                struct_generics,
+                &input.generics,
+                &type_state_info,
+                impl_docs,
+                items,
+            )
+        }
+        _ => quote! {},
+    };
+
+    // Check the state graph, if requested
+    let reachability = match (args.reachability, &graph) {
+        (Some(reachability), Some(graph)) => report(
+            &analyze(graph, &type_state_info),
+            reachability,
+            &struct_name,
+        ),
+        _ => quote! {},
+    };
+
+    // A block with `runtime`, `dot`, `mermaid`, `json` or `reachability` needs all of the methods of the struct, so it has to be the only block:
+    // it implements the trait for every `Block`, which conflicts with the implementation of any other block
+    // (the error is shown on the `impl` blocks)
+    let single_block_name = single_block_ident(&struct_name);
+    let single_block = if args.runtime || exports_graph || args.reachability.is_some() {
+        quote_spanned! {input.self_ty.span()=>
+            impl<Block> #single_block_name<Block> for () {}
+        }
+    } else {
+        quote_spanned! {input.self_ty.span()=>
+            const _: () = {
+                #[allow(dead_code)]
+                struct Block;
+                impl #single_block_name<Block> for () {}
+            };
+        }
+    };
+
     // Generate the expanded code with unique modules and traits
     let expanded = quote! {
+        #single_block
+
         #(#methods)*
+
+        #runtime
+
+        #graph_consts
+
+        #reachability
     };
 
-    expanded.into()
+    Ok(expanded)
+}
+
+/// Applies `#[require]` and `#[switch_to]` to a method, `None` if it doesn't have `#[require]`
+///
+/// Returns the modified method, and its states for the rest of the block (the runtime mirror, the graph...).
+fn expand_method(
+    method: &mut ImplItemFn,
+    struct_name: &Ident,
+    struct_generics: &PathArguments,
+    impl_generics: &Generics,
+    type_state_info: &TypeStateInfo,
+) -> syn::Result<Option<(proc_macro2::TokenStream, MethodTransition)>> {
+    // Extract `#[require]` arguments if they exist
+    let Some(require_args) = extract_macro_args(&mut method.attrs, "require")? else {
+        return Ok(None);
+    };
+    let fn_name = &method.sig.ident;
+
+    // Convert the arguments to the positional form: `#[require(State1, B, C)]`
+    let (required, generics) =
+        resolve_state_args(&require_args, None, struct_name, type_state_info, fn_name)?;
+    check_slot_states(&required, &generics, type_state_info, fn_name)?;
+
+    // the slots that are not mentioned in `#[switch_to]` keep their required states
+    let mut keep_on_err = false;
+    let switched_to = match extract_macro_args(&mut method.attrs, "switch_to")? {
+        Some(switch_to_args) => {
+            let switch_to_args = take_on_err(switch_to_args, &mut keep_on_err, fn_name)?;
+            let unmentioned: Vec<Ident> = required.iter().cloned().collect();
+            let (switched_to, _) = resolve_state_args(
+                &switch_to_args,
+                Some(&unmentioned),
+                struct_name,
+                type_state_info,
+                fn_name,
+            )?;
+            check_slot_states(&switched_to, &generics, type_state_info, fn_name)?;
+            check_transitions(
+                &required,
+                &switched_to,
+                &generics,
+                type_state_info,
+                struct_name,
+                fn_name,
+            )?;
+            Some(switched_to)
+        }
+        None => None,
+    };
+
+    let transition = MethodTransition {
+        sig: method.sig.clone(),
+        visibility: method.vis.clone(),
+        required,
+        switched_to,
+        generics,
+        keep_on_err,
+    };
+
+    let modified_method = generate_impl_block_for_method_based_on_require_args(
+        method,
+        struct_name,
+        &transition,
+        impl_generics,
+        struct_generics,
+        type_state_info,
+    )?;
+
+    Ok(Some((modified_method, transition)))
+}
+
+/// An `impl` block for the struct in its default states: `impl PlayerBuilder<Initial> { ... }`
+fn generate_default_state_impl(
+    struct_name: &Ident,
+    struct_generics: &PathArguments,
+    impl_generics: &Generics,
+    info: &TypeStateInfo,
+    attrs: proc_macro2::TokenStream,
+    items: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut args = match struct_generics {
+        PathArguments::AngleBracketed(angle_bracketed) => angle_bracketed.args.clone(),
+        _ => Punctuated::new(),
+    };
+    for slot in &info.slots {
+        let default = Ident::new(&slot.default, struct_name.span());
+        args.push(parse_quote!(#default));
+    }
+    let (impl_generics, _, where_clause) = impl_generics.split_for_impl();
+
+    quote! {
+        #attrs
+        impl #impl_generics #struct_name<#args> #where_clause {
+            #items
+        }
+    }
 }
@@ -0,0 +1,213 @@
+// `#[impl_state]` needs to know how the struct was declared in `#[type_state]`
+// (e.g. the names and the order of the slots, so that `#[require(a = State)]` can be expanded).
+//
+// Procedural macros cannot pass information to each other directly, so `#[type_state]` generates a `macro_rules!`
+// next to the struct, which holds a plain description of the struct (see `TypeStateInfo`), and re-exports it with the name of the struct.
+// `#[impl_state]` expands into a call of that macro: `PlayerBuilder! { ... }`, which calls back into `#[impl_state]` with the description.
+//
+// So the description is found by the same path as the struct itself (the structs with the same name in different modules don't mix up),
+// and the compiler waits for `#[type_state]` to be expanded, whatever the order of the items is.
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    bracketed, parenthesized,
+    parse::{Parse, ParseStream},
+    LitBool, LitStr, Token,
+};
+
+/// What `#[impl_state]` needs to know about a `#[type_state]` struct
+#[derive(Clone)]
+pub struct TypeStateInfo {
+    /// All of the states: `states = (Initial, RaceSet, ...)`, and the generated states of the flags
+    pub states: Vec<String>,
+    pub slots: Vec<SlotInfo>,
+    /// `Some` if the struct declares its transitions: `transitions = (Initial -> RaceSet, ...)`
+    pub transitions: Option<Vec<TransitionInfo>>,
+    /// The visibility of the struct: `pub(crate)`, empty for the private structs
+    pub visibility: String,
+    /// `Some` if `Any{StructName}` is generated: the state combinations of its variants
+    pub any_variants: Option<Vec<Vec<String>>>,
+    /// `true` if the transitions call the `OnEnter{StructName}`/`OnExit{StructName}` hooks: `hooks = true`
+    pub hooks: bool,
+    /// The states that the objects have to end in: `terminal = (Committed, RolledBack)`
+    pub terminal: Vec<String>,
+    /// The invariants of the states: `#[invariant(LevelSet => self.level.is_some())]`
+    pub invariants: Vec<InvariantInfo>,
+    /// The methods of the `#[impl_state]` block, empty in `#[type_state]`
+    pub methods: Vec<MethodInfo>,
+}
+
+#[derive(Clone)]
+pub struct SlotInfo {
+    /// `Some` if the slot is declared with a name: `slots = (race: Initial, ...)`
+    pub name: Option<String>,
+    /// The default state of the slot: `race: Initial`
+    pub default: String,
+    /// `Some` if the slot lists the states it accepts: `slots = (race: Initial in (Initial, RaceSet), ...)`
+    pub states: Option<Vec<String>>,
+    /// `true` if the slot is declared as a flag: `flags = (race, ...)`
+    pub is_flag: bool,
+}
+
+#[derive(Clone)]
+pub struct TransitionInfo {
+    /// `None` for a transition from any state: `_ -> Initial`
+    pub from: Option<String>,
+    pub to: String,
+}
+
+#[derive(Clone)]
+pub struct InvariantInfo {
+    pub state: String,
+    /// The condition as written, with `self` standing for the object in this state
+    pub condition: String,
+}
+
+/// A method with `#[require]`, recorded by `#[impl_state]`
+#[derive(Clone)]
+pub struct MethodInfo {
+    pub name: String,
+    /// The positional form of `#[require]`: `(Initial, B)`
+    pub required: Vec<String>,
+    /// The positional form of `#[switch_to]`, `None` if the method doesn't have it
+    pub switched_to: Option<Vec<String>>,
+    /// The generics among the required states: `B`
+    pub generics: Vec<String>,
+    /// The visibility of the method: `pub(crate)`, empty for the private methods
+    pub visibility: String,
+    /// `true` if the method doesn't take `self`, so it creates the objects in its required states
+    pub is_constructor: bool,
+}
+
+/// The description is passed as tokens: the strings as literals, `[...]` for the lists, `None`/`Some(...)`,
+/// and `(...)` for the structs, with their fields in order
+trait Encode: Sized {
+    fn encode(&self) -> TokenStream;
+    fn decode(input: ParseStream) -> syn::Result<Self>;
+}
+
+impl Encode for String {
+    fn encode(&self) -> TokenStream {
+        quote!(#self)
+    }
+
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(input.parse::<LitStr>()?.value())
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self) -> TokenStream {
+        quote!(#self)
+    }
+
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        Ok(input.parse::<LitBool>()?.value)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self) -> TokenStream {
+        let items = self.iter().map(Encode::encode);
+        quote!([#(#items),*])
+    }
+
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        bracketed!(content in input);
+        let mut items = Vec::new();
+        while !content.is_empty() {
+            items.push(T::decode(&content)?);
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self) -> TokenStream {
+        match self {
+            Some(value) => {
+                let value = value.encode();
+                quote!(Some(#value))
+            }
+            None => quote!(None),
+        }
+    }
+
+    fn decode(input: ParseStream) -> syn::Result<Self> {
+        let variant: syn::Ident = input.parse()?;
+        if variant == "None" {
+            return Ok(None);
+        }
+        let content;
+        parenthesized!(content in input);
+        Ok(Some(T::decode(&content)?))
+    }
+}
+
+macro_rules! encode_struct {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl Encode for $name {
+            fn encode(&self) -> TokenStream {
+                let fields = [$(self.$field.encode()),*];
+                quote!((#(#fields),*))
+            }
+
+            fn decode(input: ParseStream) -> syn::Result<Self> {
+                let content;
+                parenthesized!(content in input);
+                $(
+                    let $field = Encode::decode(&content)?;
+                    if !content.is_empty() {
+                        content.parse::<Token![,]>()?;
+                    }
+                )*
+                Ok($name { $($field),* })
+            }
+        }
+    };
+}
+
+encode_struct!(TypeStateInfo {
+    states,
+    slots,
+    transitions,
+    visibility,
+    any_variants,
+    hooks,
+    terminal,
+    invariants,
+    methods,
+});
+encode_struct!(SlotInfo {
+    name,
+    default,
+    states,
+    is_flag,
+});
+encode_struct!(TransitionInfo { from, to });
+encode_struct!(InvariantInfo { state, condition });
+encode_struct!(MethodInfo {
+    name,
+    required,
+    switched_to,
+    generics,
+    visibility,
+    is_constructor,
+});
+
+impl TypeStateInfo {
+    /// The description as tokens, for the macro generated by `#[type_state]`
+    pub fn encode(&self) -> TokenStream {
+        Encode::encode(self)
+    }
+}
+
+impl Parse for TypeStateInfo {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Encode::decode(input)
+    }
+}
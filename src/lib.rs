@@ -13,17 +13,29 @@
 //! - `#[switch_to]`: Modifies the return type of methods to switch between states.
 //! - `#[impl_state]`: Defines the valid states for a given type and generates corresponding marker structs and trait implementations.
 //! - `#[type_state]`: Transforms the struct into type-state compatible form, using state slots and default states.
+//! - `#[derive(TypeStateBuilder)]`: Generates a type-state builder for a plain struct, using the macros above.
 
 extern crate proc_macro;
 
+mod any_state;
+mod builder;
+mod graph;
 mod helper;
 mod impl_state;
+mod info;
+mod model;
+mod reachability;
 mod require;
+mod runtime;
 mod switch_to;
 mod type_state;
 
-use helper::{extract_idents_from_group, extract_macro_args, is_single_letter};
-use impl_state::impl_state_inner;
+use builder::type_state_builder_inner;
+use helper::{
+    check_slot_states, check_transitions, drop_check_ident, extract_macro_args, flag_state_ident,
+    generic_args, resolve_state_args, single_block_ident, slot_generic_ident, slot_sealer_ident,
+};
+use impl_state::{impl_state_inner, impl_state_with_info_inner};
 use require::generate_impl_block_for_method_based_on_require_args;
 use switch_to::switch_to_inner;
 use type_state::type_state_inner;
@@ -32,17 +44,70 @@ use proc_macro::TokenStream;
 
 /// Turns your struct into type-state compatible version.
 ///
-/// Usage: `#[type_state(states = (State1, State2, ...), slots = (DefaultState, ...))]`
+/// Usage:
+/// - `#[type_state(states = (State1, State2, ...), slots = (DefaultState, ...))]`
+/// - or with named slots: `#[type_state(states = (State1, State2, ...), slots = (slot_name: DefaultState, ...))]`
 ///
 /// Arguments:
 /// - `states` -> A list of the states that the struct can transition through, which will be generated as marker structs and traits.
 /// - `slots` -> Specifies the default states for the struct's state slots. Each slot corresponds to a tracked state.
+///   Slots can be named, so that `#[require]` and `#[switch_to]` can refer to them by their names.
+///   Slots can also list the states they accept: `slots = (race: Initial in (Initial, RaceSet), ...)`,
+///   in which case each slot gets its own sealer trait, and `states` can be omitted.
+/// - `flags` (optional) -> A shorthand for "has this been set?" slots: `flags = (race, level)`.
+///   Generates the `RaceUnset`/`RaceSet` states and a `race: RaceUnset in (RaceUnset, RaceSet)` slot for each flag.
+///   `slots` and `states` can be omitted when `flags` is provided.
+/// - `transitions` (optional) -> The allowed transitions: `transitions = (Initial -> RaceSet, RaceSet -> LevelSet, _ -> Initial)`.
+///   `_` stands for any state. If provided, `#[impl_state]` rejects any method whose `#[require]`/`#[switch_to]` pair is not declared here.
+/// - `any = true` (optional) -> Generates the type-erased `Any{StructName}` enum, with a variant for each reachable state combination,
+///   `From` implementations for each typed state, `try_into_{state}()` downcasts,
+///   and the `{StructName}State` enum, which mirrors the states at runtime. At most 256 state combinations are supported.
+/// - `cell = true` (optional, requires `any = true`) -> Generates `{StructName}Cell`, which holds `Any{StructName}` (e.g. behind `&mut` in a container),
+///   and lends the object in a state as a typed `{StructName}Guard`: `cell.in_state::<Initial>()`.
+///   The guard derefs to the typed object, `guard.switch(|object| object.transition())` switches it to the new states in place,
+///   and the object is put back into the cell when the guard is dropped.
+/// - `debug = true` (optional) -> Generates a `Debug` implementation, which shows the fields and the states instead of `_state`:
+///   `PlayerBuilder { race: None, level: None, state: "Initial" }`, or `state: ["NameUnset", "ItemsSet"]` for multiple slots.
+/// - `hooks = true` (optional) -> Generates the `OnEnter{StructName}<Object>` and `OnExit{StructName}<Object>` traits,
+///   which have to be implemented for each state (the default methods are empty).
+///   `hooks = (State1, ...)` only requires them for the listed states, the others get the empty ones.
+///   The transitions (`#[switch_to]` methods that take `self`) call them for each slot that changes its state:
+///   `<Old as OnExit..>::on_exit::<New>(&old_object)`, then `<New as OnEnter..>::on_enter::<Old>(&mut new_object)`.
+///   They have to return the struct, `Option<Struct>` or `Result<Struct, _>` (`on_enter` is only called for `Some` and `Ok`),
+///   the other transitions (e.g. `async`) are rejected. With `on_err = keep`, both of them are only called for `Ok`,
+///   and `on_exit` gets the object in its new states.
+/// - `terminal = (State1, ...)` (optional) -> The states that the objects have to end in. In debug builds, dropping an object
+///   in a non-terminal state panics (at least one of its slots has to be in a terminal state). The methods that take `self`
+///   and return the struct or switch to a terminal state, `into_parts()` and `assume_state()` (with `parts = true`)
+///   consume the object, so it is not dropped in its state,
+///   unless they pass `self` on as a whole (`return self`, `Err(self)`, `Transaction::commit(self)`). The struct cannot be `Copy`.
+/// - `parts = true` (optional) -> Generates the `{StructName}Parts` struct (the fields without `_state`), `into_parts(self)`,
+///   and the `unsafe` `from_parts(parts)` and `assume_state::<...>(self)`, so that the objects can be rebuilt in a state
+///   (e.g. after loading them from disk) without writing the `_state` field by hand.
+/// - `state_names = true` (optional) -> Generates the `state_names(&self)` method, which returns the names of the current states of the slots
+///   (no runtime data is stored): `["RaceSet"]`, one entry per slot.
+///
+/// Invariants (optional, after `#[type_state]`): `#[invariant(LevelSet => self.level.is_some())]`
+/// - Checked with `debug_assert!` whenever an object enters the state through an `#[impl_state]` method
+///   (a `#[switch_to]` method, or a constructor). Compiled out in release builds.
+/// - The method has to return the struct, `Option<Struct>` or `Result<Struct, _>` (only `Some` and `Ok` are checked),
+///   the other methods that enter the state (e.g. `async`) are rejected.
+/// - `self` stands for the object, `self::` is still a path: `#[invariant(LevelSet => self::is_valid(&self.level))]`.
 ///
 /// What it does:
 /// - Defines the valid states that a struct can transition between using the `states` attribute,
 /// - Configures multiple state slots if needed, allowing a struct to track multiple states concurrently,
 /// - Protects against invalid struct initialization by sealing state transitions using traits and marker structs,
 /// - Seals the trait implementations for each state to ensure safety and prevent external modification.
+/// - Derives `Clone`, `Copy`, `Debug`, `PartialEq`, `Eq`, `Hash` and `Default` on the markers (and requires them in `Sealer{StructName}`),
+///   so that these derives on the struct also work with the state generics.
+/// - Seals each slot that lists its own states, so that a state cannot be put into a slot that it doesn't belong to.
+/// - Adds `#[must_use = "state transition result must be used"]` to the struct, unless it already has a `#[must_use]`.
+/// - Generates the `StateName{StructName}` trait, which gives each state its name: `<RaceSet as StateNamePlayerBuilder>::NAME`,
+/// - With the `serde` feature: skips `_state` in the serde derives of the struct, and applies them to `Any{StructName}` as well,
+///   which is tagged with the state: `{ "state": "RaceSet", ... }`.
+///   The typed struct doesn't store its state, so it is deserialized in any state that is asked for:
+///   deserialize `Any{StructName}` instead if the data is not trusted to be in that state.
 #[proc_macro_attribute]
 pub fn type_state(args: TokenStream, input: TokenStream) -> TokenStream {
     type_state_inner(args, input)
@@ -50,20 +115,117 @@ pub fn type_state(args: TokenStream, input: TokenStream) -> TokenStream {
 
 /// Modifies the methods in an `impl` block to work with the type-state pattern.
 ///
-/// Usage: `#[impl_state]`
+/// Usage: `#[impl_state]`, or with arguments: `#[impl_state(runtime = true, dot = true, mermaid = true, json = true)]`
+///
+/// The struct has to be declared with `#[type_state]`, which generates a hidden macro with the name of the struct:
+/// `#[impl_state]` expands into a call of it to get the declaration (the slots, the transitions, the hooks...).
+/// So the struct has to be in scope by its name, and the order of the items doesn't matter.
+///
+/// Arguments:
+/// - `runtime = true` (optional) -> Generates the runtime mirror of the transitions (requires `#[type_state(any = true)]`):
+///   the `{StructName}Event` enum with a variant for each method that takes `self` and returns the struct,
+///   and the `Any{StructName}::transition(self, event)` dispatcher, which rejects the illegal transitions with `{StructName}TransitionError` (a `std::error::Error`).
+///   The dispatcher needs all of the transitions, so the block with `runtime = true` has to be the only `#[impl_state]` block of the struct
+///   (another block is rejected with a conflicting implementation of `SingleImplStateBlock{StructName}`).
+/// - `dot = true` (optional) -> Generates the `STATE_GRAPH_DOT` constant on the struct (in its default states):
+///   the states and the transitions as a Graphviz DOT diagram, a cluster for each slot, with the methods as the edge labels.
+///   The graph needs all of the methods, so the block with `dot`, `mermaid` or `json` has to be the only `#[impl_state]` block of the struct,
+///   like with `runtime = true`.
+/// - `mermaid = true` (optional) -> The same graph as a Mermaid `stateDiagram-v2`: the `STATE_GRAPH_MERMAID` constant,
+///   which is also the doc comment of its `impl` block, so that the diagram shows up in rustdoc.
+/// - `json = true` (optional) -> Generates the `STATE_MODEL_JSON` constant: the states, the slots (with their defaults),
+///   the declared transitions, and the methods (name, visibility, required and target states) as JSON, for the external tools.
+///   The schema is versioned by its `version` field.
+/// - `reachability = warn` or `reachability = deny` (optional) -> Checks the state graph,
+///   and reports the states that cannot be reached from the default states (or the constructors), the dead ends (no method can be called in them),
+///   and the declared states that no slot uses. `warn` reports them as `deprecated` warnings, `deny` as errors.
+///   Like the graphs, the check needs all of the methods, so it has to be on the only `#[impl_state]` block of the struct.
+///
+/// If the `STATE_SHIFT_GRAPH_DIR` environment variable is set at compile time, the blocks with `dot`, `mermaid` or `json`
+/// also write the diagrams and the model into that directory: `{StructName}.dot`, `{StructName}.mmd` and `{StructName}.json`.
 ///
 /// What it does:
 /// - Applies type-state-specific transformations to methods in an `impl` block,
 /// - Enforces state requirements on methods with the `#[require]` macro,
 /// - Transforms methods that transition between states using the `#[switch_to]` macro,
-/// - Automatically adds the hidden `_state` field to the `Self {}` struct initialization, ensuring compliance with the type-state pattern.
+/// - Automatically adds the hidden `_state` field to the struct initializations (`StructName { ... }`) anywhere in the body,
+///   including the `async` blocks and the early `return`s, ensuring compliance with the type-state pattern.
+/// - Injects the states into the struct wherever it appears in the return type: `Option<StructName>`, `impl Future<Output = StructName>`,
+///   `Pin<Box<dyn Future<Output = StructName>>>`, and into the output of the `async fn`s.
+/// - Rejects the transitions that are not declared in the `transitions` of `#[type_state]` (if provided).
+/// - Adds `#[must_use = "state transition result must be used"]` to the methods that change the state, unless they already have a `#[must_use]`
+///   or they return the struct itself or a future (which are already `#[must_use]`).
+///
+/// So throwing away the object in its new state is reported, whether it is returned as it is or wrapped:
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// use state_shift::{impl_state, type_state};
+///
+/// #[type_state(states = (Initial, LevelSet), slots = (Initial))]
+/// struct PlayerBuilder {
+///     level: Option<u8>,
+/// }
+///
+/// #[impl_state]
+/// impl PlayerBuilder {
+///     #[require(Initial)]
+///     fn new() -> PlayerBuilder {
+///         PlayerBuilder { level: None }
+///     }
+///
+///     #[require(Initial)]
+///     #[switch_to(LevelSet)]
+///     fn set_level(self, level: u8) -> PlayerBuilder {
+///         PlayerBuilder { level: Some(level) }
+///     }
+/// }
+///
+/// fn main() {
+///     PlayerBuilder::new().set_level(10); // error: unused `PlayerBuilder` that must be used
+/// }
+/// ```
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// use state_shift::{impl_state, type_state};
+///
+/// #[type_state(states = (Initial, LevelSet), slots = (Initial))]
+/// struct PlayerBuilder {
+///     level: Option<u8>,
+/// }
+///
+/// #[impl_state]
+/// impl PlayerBuilder {
+///     #[require(Initial)]
+///     fn new() -> PlayerBuilder {
+///         PlayerBuilder { level: None }
+///     }
+///
+///     #[require(Initial)]
+///     #[switch_to(LevelSet)]
+///     fn try_set_level(self, level: u8) -> Option<PlayerBuilder> {
+///         (level <= 100).then(|| PlayerBuilder { level: Some(level) })
+///     }
+/// }
+///
+/// fn main() {
+///     PlayerBuilder::new().try_set_level(10); // error: unused return value that must be used
+/// }
+/// ```
 ///
 /// Also:
 /// - Consumes the `#[require]` and `#[switch_to]` macros and handles the necessary transformations for those macros,
 /// - Ensures that the methods only execute in the correct state and can safely transition between valid states.
 #[proc_macro_attribute]
-pub fn impl_state(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    impl_state_inner(item)
+pub fn impl_state(attr: TokenStream, item: TokenStream) -> TokenStream {
+    impl_state_inner(attr, item)
+}
+
+/// The second half of `#[impl_state]`, called by the macro that `#[type_state]` generates with the name of the struct,
+/// with the description of the struct. Not meant to be used directly.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __impl_state_with_info(input: TokenStream) -> TokenStream {
+    impl_state_with_info_inner(input)
 }
 
 /// Denotes which state is required for this method to be called.
@@ -71,6 +233,8 @@ pub fn impl_state(_attr: TokenStream, item: TokenStream) -> TokenStream {
 /// Usage:
 /// - `#[require(State1)]`
 /// - or with multiple state slots: `#[require(State1, State2, ...)]`
+/// - or with named slots: `#[require(slot_name = State1, ...)]`, the slots that are not mentioned can be in any state
+/// - or with flags: `#[require(race, level)]`, meaning `race` and `level` are set, the others can be in any state
 ///
 /// This macro is consumed by the `#[impl_state]` macro, and it basically guides `#[impl_state]` macro to:
 /// - generate a specific `impl` block for each method,
@@ -94,6 +258,13 @@ pub fn require(_args: TokenStream, _input: TokenStream) -> TokenStream {
 /// Usage:
 /// - `#[switch_to(State1)]`
 /// - or with multiple state slots: `#[switch_to(State1, State2, ...)]`
+/// - or with named slots: `#[switch_to(slot_name = State1, ...)]`, the slots that are not mentioned keep their states
+/// - or with flags: `#[switch_to(race)]`, meaning `race` becomes set, the others keep their states
+/// - and `on_err = keep` in any of the above: `#[switch_to(Open, on_err = keep)]`, the struct in the error of a `Result`
+///   keeps the required states, so that a failed transition can give the object back: `Result<Conn, (Conn, io::Error)>`
+///
+/// Only the occurrences of the struct without states get the new states, so the others can be written with their own:
+/// `-> (PlayerBuilder, PlayerBuilder<Initial>)`
 ///
 /// This macro is consumed by the `#[impl_state]` macro, and it basically guides `#[impl_state]` macro to:
 /// - overwrite the return type of the methods generated by the `#[impl_state]` macro
@@ -107,3 +278,40 @@ pub fn switch_to(_args: TokenStream, _input: TokenStream) -> TokenStream {
         "`#[switch_to]` macro should not be imported. It is consumed by the `#[impl_state]` macro."
     );
 }
+
+/// Generates a type-state builder for a plain struct: `Player` -> `PlayerBuilder`
+///
+/// Usage:
+/// ```ignore
+/// #[derive(TypeStateBuilder)]
+/// struct Player {
+///     race: Race,
+///     level: u8,
+///     #[builder(default)]
+///     score: u32,
+///     #[builder(optional)]
+///     nickname: Option<String>,
+/// }
+///
+/// let player = Player::builder().level(10).race(Race::Orc).build();
+/// ```
+///
+/// What it does:
+/// - Generates `PlayerBuilder` with `#[type_state]`, where each required field is a flag prefixed with the struct name:
+///   `player_race`, with the states `PlayerRaceUnset` and `PlayerRaceSet`,
+/// - Generates a setter for each field, the setters of the required fields switch their flag to set (with `#[impl_state]`),
+/// - Generates `build()`, which is only available when all of the required fields are set,
+/// - Generates `Player::builder()`, which returns the builder with none of the fields set.
+///
+/// Field attributes:
+/// - `#[builder(default)]` -> The field can be skipped, and it will be `Default::default()`,
+/// - `#[builder(optional)]` -> The field (an `Option<T>`) can be skipped, and it will be `None`. Its setter takes `T`.
+///
+/// Struct attributes:
+/// - `#[builder(sequence = (race, level))]` -> The required fields have to be set in this order.
+///   Instead of the flags, the builder has a single slot with the states `PlayerInitial`, `PlayerRaceSet`, `PlayerLevelSet`,
+///   and the transitions between them are declared in `#[type_state]`. All of the required fields have to be listed.
+#[proc_macro_derive(TypeStateBuilder, attributes(builder))]
+pub fn type_state_builder(input: TokenStream) -> TokenStream {
+    type_state_builder_inner(input)
+}
@@ -0,0 +1,206 @@
+// this file contains the logic that exports the typestate model of a struct as JSON,
+// so that external tools (linters, test generators, documentation sites...) don't have to parse Rust.
+//
+// The schema is versioned by its `version` field, which is bumped on breaking changes:
+// {
+//   "version": 1,
+//   "name": "PlayerBuilder",
+//   "visibility": "pub",
+//   "states": ["Initial", "RaceSet"],
+//   "slots": [{ "name": "race", "default": "Initial", "states": ["Initial", "RaceSet"], "flag": false }],
+//   "transitions": [{ "from": "Initial", "to": "RaceSet" }],
+//   "terminal": [],
+//   "methods": [{ "name": "set_race", "visibility": "pub", "required": ["Initial"], "switched_to": ["RaceSet"], "generics": [] }]
+// }
+//
+// - `name` of a positional slot, `states` of a slot that doesn't list its states, `transitions` if they are not declared,
+//   `from` of a transition from any state, and `switched_to` of a method without `#[switch_to]` are `null`,
+// - `visibility` is empty for the private items,
+// - `required` and `switched_to` are positional, the generic states (`#[require(A)]`) are listed in `generics`.
+use std::fmt::Write;
+
+use crate::info::TypeStateInfo;
+
+/// The version of the JSON schema
+pub const MODEL_VERSION: u32 = 1;
+
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u32),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn string(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+
+    fn strings(values: &[String]) -> Json {
+        Json::Array(values.iter().map(|value| Json::string(value)).collect())
+    }
+
+    fn optional<T>(value: Option<T>, f: impl FnOnce(T) -> Json) -> Json {
+        value.map_or(Json::Null, f)
+    }
+
+    /// Pretty-printed with 2 spaces, the arrays of strings are kept on a single line
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(value) => write!(out, "{}", value).unwrap(),
+            Json::Number(value) => write!(out, "{}", value).unwrap(),
+            Json::String(value) => write_string(out, value),
+            Json::Array(values) if values.is_empty() => out.push_str("[]"),
+            Json::Array(values) if values.iter().all(|value| matches!(value, Json::String(_))) => {
+                out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    value.write(out, indent);
+                }
+                out.push(']');
+            }
+            Json::Array(values) => {
+                out.push_str("[\n");
+                for (i, value) in values.iter().enumerate() {
+                    push_indent(out, indent + 1);
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+                }
+                push_indent(out, indent);
+                out.push(']');
+            }
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    push_indent(out, indent + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                push_indent(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    out.push_str(&"  ".repeat(indent));
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The typestate model of the struct as JSON, with the methods of the `#[impl_state]` blocks that are expanded so far
+pub fn to_json(struct_name: &str, info: &TypeStateInfo) -> String {
+    let slots = info
+        .slots
+        .iter()
+        .map(|slot| {
+            Json::Object(vec![
+                ("name", Json::optional(slot.name.as_deref(), Json::string)),
+                ("default", Json::string(&slot.default)),
+                (
+                    "states",
+                    Json::optional(slot.states.as_deref(), Json::strings),
+                ),
+                ("flag", Json::Bool(slot.is_flag)),
+            ])
+        })
+        .collect();
+
+    let transitions = Json::optional(info.transitions.as_ref(), |transitions| {
+        Json::Array(
+            transitions
+                .iter()
+                .map(|transition| {
+                    Json::Object(vec![
+                        (
+                            "from",
+                            Json::optional(transition.from.as_deref(), Json::string),
+                        ),
+                        ("to", Json::string(&transition.to)),
+                    ])
+                })
+                .collect(),
+        )
+    });
+
+    let methods = info
+        .methods
+        .iter()
+        .map(|method| {
+            Json::Object(vec![
+                ("name", Json::string(&method.name)),
+                ("visibility", Json::string(&method.visibility)),
+                ("required", Json::strings(&method.required)),
+                (
+                    "switched_to",
+                    Json::optional(method.switched_to.as_deref(), Json::strings),
+                ),
+                ("generics", Json::strings(&method.generics)),
+            ])
+        })
+        .collect();
+
+    let model = Json::Object(vec![
+        ("version", Json::Number(MODEL_VERSION)),
+        ("name", Json::string(struct_name)),
+        ("visibility", Json::string(&info.visibility)),
+        ("states", Json::strings(&info.states)),
+        ("slots", Json::Array(slots)),
+        ("transitions", transitions),
+        ("terminal", Json::strings(&info.terminal)),
+        ("methods", Json::Array(methods)),
+    ]);
+
+    let mut json = String::new();
+    model.write(&mut json, 0);
+    json.push('\n');
+    json
+}
+
+/// The visibility as it is written: `pub(crate)`, empty for the private items
+pub fn visibility_string(visibility: &syn::Visibility) -> String {
+    match visibility {
+        syn::Visibility::Public(_) => "pub".to_string(),
+        syn::Visibility::Restricted(restricted) => {
+            let path = restricted
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::");
+            let path = if restricted.path.leading_colon.is_some() {
+                format!("::{}", path)
+            } else {
+                path
+            };
+            match restricted.in_token {
+                Some(_) => format!("pub(in {})", path),
+                None => format!("pub({})", path),
+            }
+        }
+        syn::Visibility::Inherited => String::new(),
+    }
+}
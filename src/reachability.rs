@@ -0,0 +1,120 @@
+// this file contains the reachability analysis of `#[impl_state(reachability = warn)]`,
+// which looks for the mistakes in the state machine that the type system doesn't catch:
+// - the states that cannot be reached from the default states of the slots (or from the constructors),
+//   so the methods that require them can never be called,
+// - the reachable states that are dead ends: no method can be called in them, and they are not `terminal`,
+// - the declared states that are not used by any slot.
+//
+// The analysis is done on the state graph, so it needs all of the methods of the struct:
+// the block with `reachability` has to be the only `#[impl_state]` block (see `single_block_ident`).
+//
+// Procedural macros cannot emit warnings on stable Rust, so each finding is reported
+// as the use of a `#[deprecated]` item, with the finding as its note. `reachability = deny` turns them into errors.
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::Ident;
+
+use crate::{
+    graph::StateGraph,
+    info::{MethodInfo, TypeStateInfo},
+};
+
+/// How the findings are reported
+#[derive(Clone, Copy, PartialEq)]
+pub enum Reachability {
+    /// `reachability = warn`
+    Warn,
+    /// `reachability = deny`
+    Deny,
+}
+
+/// The findings of the analysis, as messages
+pub fn analyze(graph: &StateGraph, info: &TypeStateInfo) -> Vec<String> {
+    let mut findings = Vec::new();
+
+    for (i, slot) in graph.slots.iter().enumerate() {
+        let is_generic = |method: &MethodInfo| method.generics.contains(&method.required[i]);
+        let slot_name = match &info.slots[i].name {
+            Some(name) => format!("slot `{}`", name),
+            None => slot.label.clone(),
+        };
+
+        // the objects start in the default state, or in the states of the constructors
+        let mut reachable: Vec<&String> = vec![&slot.default];
+        for method in info.methods.iter().filter(|method| method.is_constructor) {
+            if !is_generic(method) && !reachable.contains(&&method.required[i]) {
+                reachable.push(&method.required[i]);
+            }
+        }
+        let mut next = 0;
+        while next < reachable.len() {
+            let state = reachable[next];
+            for edge in slot.edges.iter().filter(|edge| &edge.from == state) {
+                if !reachable.contains(&&edge.to) {
+                    reachable.push(&edge.to);
+                }
+            }
+            next += 1;
+        }
+
+        for state in &slot.states {
+            let methods: Vec<&str> = info
+                .methods
+                .iter()
+                .filter(|method| !is_generic(method) && &method.required[i] == state)
+                .map(|method| method.name.as_str())
+                .collect();
+
+            if !reachable.contains(&state) {
+                let required_by = if methods.is_empty() {
+                    String::new()
+                } else {
+                    format!(", so `{}` can never be called", methods.join("`, `"))
+                };
+                findings.push(format!(
+                    "`{}` of {} is unreachable from its default state `{}`{}",
+                    state, slot_name, slot.default, required_by
+                ));
+            } else if methods.is_empty()
+                && !info.methods.iter().any(is_generic)
+                && !info.terminal.contains(state)
+            {
+                findings.push(format!(
+                    "`{}` of {} is a dead end: no method can be called in it",
+                    state, slot_name
+                ));
+            }
+        }
+    }
+
+    for state in &info.states {
+        if graph.slots.iter().all(|slot| !slot.states.contains(state)) {
+            findings.push(format!("`{}` is declared, but no slot uses it", state));
+        }
+    }
+
+    findings
+}
+
+/// The findings as warnings (uses of `#[deprecated]` items) or as errors, pointing at the `impl` block
+pub fn report(findings: &[String], reachability: Reachability, struct_name: &Ident) -> TokenStream {
+    let span = struct_name.span();
+    findings
+        .iter()
+        .map(|finding| {
+            let message = format!("`{}`: {}", struct_name, finding);
+            match reachability {
+                Reachability::Warn => quote_spanned! {span=>
+                    const _: () = {
+                        #[deprecated(note = #message)]
+                        struct StateShiftReachability;
+                        let _ = StateShiftReachability;
+                    };
+                },
+                Reachability::Deny => quote_spanned! {span=>
+                    ::core::compile_error!(#message);
+                },
+            }
+        })
+        .collect()
+}
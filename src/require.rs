@@ -1,26 +1,39 @@
 /// this file contains the logic that modifies the methods that are annotated with `#[require]` macro,
 /// however, all the functions inside this file will be used by `#[impl_state]` macro due to delegation needs
-use proc_macro2::TokenStream;
+use proc_macro2::{Group, Spacing, TokenStream, TokenTree};
 use quote::quote;
 use syn::{
-    punctuated::Punctuated, Expr, ExprStruct, GenericParam, Ident, ImplItemFn, Member, Stmt, Token,
-    TypeParam,
+    punctuated::Punctuated, visit::Visit, visit_mut::VisitMut, Attribute, Expr, ExprStruct, FnArg,
+    GenericArgument, GenericParam, Ident, ImplItemFn, Member, Pat, PathArguments, PathSegment,
+    ReturnType, Signature, Token, Type, TypeParam,
 };
 
-use crate::{extract_macro_args, is_single_letter, switch_to_inner};
+use crate::{
+    drop_check_ident, impl_state::MethodTransition, info::TypeStateInfo, slot_sealer_ident,
+    switch_to_inner,
+};
 
 pub fn generate_impl_block_for_method_based_on_require_args(
     input_fn: &mut ImplItemFn,
     struct_name: &Ident,
-    parsed_args: &Punctuated<Ident, Token![,]>,
+    transition: &MethodTransition,
     impl_generics: &syn::Generics,
     struct_generics: &syn::PathArguments,
-) -> proc_macro2::TokenStream {
+    type_state_info: &TypeStateInfo,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let parsed_args = &transition.required;
+    let generic_args = &transition.generics;
+
     // Convert the struct's generics into a Punctuated collection
     let mut combined_generics = match struct_generics {
         syn::PathArguments::AngleBracketed(angle_bracketed) => angle_bracketed.args.clone(),
         syn::PathArguments::None => Punctuated::new(),
-        _ => panic!("Unsupported generics format for struct"),
+        syn::PathArguments::Parenthesized(arguments) => {
+            return Err(syn::Error::new_spanned(
+                arguments,
+                "Unsupported generics format for struct",
+            ))
+        }
     };
 
     // Append the full list of arguments from `#[require]` macro: (A, B, State1, ...)
@@ -38,29 +51,34 @@ pub fn generate_impl_block_for_method_based_on_require_args(
     A: Sealer,
     B: Sealer,
      */
+    // if a slot lists its own states, its generic is bounded by the slot's sealer trait instead
     let sealer_trait_name = Ident::new(&format!("Sealer{}", struct_name), struct_name.span());
-    let new_where_clauses: Vec<proc_macro2::TokenStream> = parsed_args
+    let new_where_clauses: Vec<proc_macro2::TokenStream> = generic_args
         .iter()
-        .filter(|ident| is_single_letter(ident))
-        .map(|ident| quote!(#ident: #sealer_trait_name))
+        .map(|ident| {
+            let mut bounds: Vec<Ident> = Vec::new();
+            for (i, _) in parsed_args
+                .iter()
+                .enumerate()
+                .filter(|(_, arg)| *arg == ident)
+            {
+                let bound = match type_state_info.slots.get(i) {
+                    Some(slot) if slot.states.is_some() => {
+                        slot_sealer_ident(struct_name, i, slot.name.as_deref())
+                    }
+                    _ => sealer_trait_name.clone(),
+                };
+                if !bounds.contains(&bound) {
+                    bounds.push(bound);
+                }
+            }
+            quote!(#ident: #(#bounds)+*)
+        })
         .collect();
 
-    // Merge with the existing where clause, if any.
-    let merged_where_clause = if let Some(existing_where) = &impl_generics.where_clause {
-        quote! {
-            #existing_where #(#new_where_clauses),*
-        }
-    } else if !new_where_clauses.is_empty() {
-        quote! {
-            where #(#new_where_clauses),*
-        }
-    } else {
-        quote! {}
-    };
-
-    // Merge the original generics with the new single-letter generics.
+    // Merge the original generics with the new generics.
     let mut all_generics = impl_generics.params.clone();
-    for ident in parsed_args.iter().filter(|i| is_single_letter(i)) {
+    for ident in generic_args {
         all_generics.push(GenericParam::Type(TypeParam::from(ident.clone())));
     }
 
@@ -69,53 +87,294 @@ pub fn generate_impl_block_for_method_based_on_require_args(
         .map(|_| quote!(::core::marker::PhantomData))
         .collect();
 
-    let phantom_expr = if phantom_data.len() == 1 {
+    // with terminal states, `_state` is the drop check guard: `DropCheckStruct(PhantomData)`
+    let has_terminal = !type_state_info.terminal.is_empty();
+    let phantom_expr = if has_terminal {
+        let drop_check = drop_check_ident(struct_name);
+        quote! { #drop_check::new() }
+    } else if phantom_data.len() == 1 {
         quote! { ::core::marker::PhantomData }
     } else {
         quote! { ( #(#phantom_data),* ) }
     };
 
     // Modify the function body to append `_state: (PhantomData, ...)` to struct fields.
-    let new_fn_body: Vec<_> = input_fn
-        .block
-        .stmts
-        .iter()
-        .map(|stmt| {
-            if let Stmt::Expr(expr, maybe_semi) = stmt {
-                if let Some(modified_expr) =
-                    modify_struct_in_expr(expr, struct_name, phantom_expr.clone())
-                {
-                    // Return the modified expression as a statement
-                    return Stmt::Expr(modified_expr, *maybe_semi);
-                }
-            }
-            stmt.clone()
-        })
-        .collect();
+    let mut new_block = input_fn.block.clone();
+    StructLiteralRewriter {
+        struct_name,
+        phantom_expr: &phantom_expr,
+    }
+    .visit_block_mut(&mut new_block);
+    // the guard of the consumed object is disarmed at the start, so it is armed again where `self` is passed on as a whole.
+    // Only the methods that return the object, or finish it in a terminal state, consume it:
+    // the other ones (`fn id(self) -> u64`) would drop it in its state
+    let switches_to_terminal = transition.switched_to.as_ref().is_some_and(|switched_to| {
+        switched_to
+            .iter()
+            .any(|state| type_state_info.terminal.contains(&state.to_string()))
+    });
+    let disarms_self = has_terminal
+        && takes_self(input_fn)
+        && (output_mentions(&input_fn.sig, &struct_name.to_string()) || switches_to_terminal);
+    if disarms_self {
+        SelfMoveRewriter.visit_block_mut(&mut new_block);
+    }
+    let new_fn_body = new_block.stmts;
 
-    // Collect other function attributes (excluding `#[require]`).
-    let mut other_attrs: Vec<_> = input_fn
-        .attrs
-        .iter()
-        .filter(|attr| !attr.path().is_ident("require"))
-        .cloned()
-        .collect();
+    // Collect other function attributes (`#[require]` and `#[switch_to]` are already extracted by `#[impl_state]`).
+    let other_attrs = &input_fn.attrs;
+
+    // the object in its new state is the only way to go on, so it shouldn't be thrown away.
+    // The struct itself is `#[must_use]`, so it is only added to the methods that return it wrapped (`Option<Struct>`...),
+    // otherwise the same mistake would be reported twice. Same for the futures and `Result`, which are already `#[must_use]`
+    let changes_state = transition
+        .switched_to
+        .as_ref()
+        .is_some_and(|switched_to| switched_to.iter().ne(parsed_args.iter()));
+    let must_use = if changes_state
+        && !has_must_use(other_attrs)
+        && !returns_struct(&input_fn.sig, struct_name)
+        && !returns_future(&input_fn.sig)
+        && !returns_result(&input_fn.sig)
+    {
+        quote!(#[must_use = "state transition result must be used"])
+    } else {
+        quote! {}
+    };
 
     let fn_output = &input_fn.sig.output;
-    let switch_to_args = extract_macro_args(&mut other_attrs, "switch_to");
 
     // Generate the impl block for the method based on the extracted #[switch_to] arguments
-    let new_output = if let Some(switch_to_args) = switch_to_args {
-        switch_to_inner(fn_output, &switch_to_args, struct_name, &input_fn.sig.ident)
+    let own_generics = match struct_generics {
+        syn::PathArguments::AngleBracketed(angle_bracketed) => angle_bracketed
+            .args
+            .iter()
+            .filter(|arg| !matches!(arg, syn::GenericArgument::Lifetime(_)))
+            .count(),
+        _ => 0,
+    };
+    let on_err_args = transition.keep_on_err.then_some(parsed_args);
+    let new_output = if let Some(switch_to_args) = &transition.switched_to {
+        switch_to_inner(
+            fn_output,
+            switch_to_args,
+            on_err_args,
+            struct_name,
+            own_generics,
+            &input_fn.sig.ident,
+        )?
     } else {
         // there is no `#[switch_to]` macro, so we use the `#[require]` macro's arguments instead
         // to keep the type same for the input and the output
-        switch_to_inner(fn_output, parsed_args, struct_name, &input_fn.sig.ident)
+        switch_to_inner(
+            fn_output,
+            parsed_args,
+            None,
+            struct_name,
+            own_generics,
+            &input_fn.sig.ident,
+        )?
+    };
+
+    // The code that runs around the state change, the body is wrapped if there is any:
+    // - the hooks (`hooks = true`): `OnExit` of the old states with the object in its old state,
+    //   and `OnEnter` of the new states with the object in its new state,
+    // - the invariants of the new states (`#[invariant(State => ...)]`), checked in debug builds.
+    let hooks = type_state_info.hooks;
+    let invariants = &type_state_info.invariants[..];
+    let takes_self = takes_self(input_fn);
+    // the generated code refers to the receiver by its own token, so that it resolves to the parameter
+    // (`#[impl_state]` is expanded through the macro of the struct, and the tokens of the macros are hygienic)
+    let self_token = input_fn
+        .sig
+        .receiver()
+        .map(|receiver| receiver.self_token)
+        .unwrap_or_default();
+    let mut hook_where_clauses = Vec::new();
+    let mut before_body = Vec::new();
+    let mut after_body = Vec::new();
+
+    // the slots that change their states, or all of them for the new objects (e.g. from the constructors)
+    let changes: Vec<(&Ident, &Ident)> = match &transition.switched_to {
+        Some(switched_to) => parsed_args
+            .iter()
+            .zip(switched_to)
+            .filter(|(from, to)| from != to)
+            .collect(),
+        None if !takes_self && output_mentions(&input_fn.sig, &struct_name.to_string()) => {
+            parsed_args.iter().zip(parsed_args).collect()
+        }
+        None => Vec::new(),
+    };
+    let calls_hooks = hooks && takes_self && !changes.is_empty();
+
+    // the object in its new state: `Struct`, `Option<Struct>` or `Result<Struct, _>`
+    let returned = match &new_output {
+        ReturnType::Type(_, new_type) => returned_object(input_fn, new_type, struct_name),
+        ReturnType::Default => None,
+    };
+    if calls_hooks && returned.is_none() {
+        return Err(syn::Error::new(
+            input_fn.sig.ident.span(),
+            format!(
+                "Function `{}`: the hooks of `{}` cannot be called around this transition, it has to return `{}`, `Option<{}>` or `Result<{}, _>`, and it cannot be `async` or `const`.",
+                input_fn.sig.ident, struct_name, struct_name, struct_name, struct_name
+            ),
+        ));
+    }
+
+    // the invariants of the new states, they are checked on the returned object
+    let entered_invariants: Vec<_> = changes
+        .iter()
+        .flat_map(|(_, to)| {
+            invariants
+                .iter()
+                .filter(move |invariant| *to == &invariant.state)
+        })
+        .collect();
+    // the transitions and the constructors have to return the object in a way that it can be checked,
+    // the other methods (e.g. `&self` methods that return a copy) are checked if they can be
+    let enters_state = transition.switched_to.is_some() || input_fn.sig.receiver().is_none();
+    if let (Some(invariant), None, true) = (entered_invariants.first(), &returned, enters_state) {
+        return Err(syn::Error::new(
+            input_fn.sig.ident.span(),
+            format!(
+                "Function `{}`: the invariant of `{}` cannot be checked on the object, it has to return `{}`, `Option<{}>` or `Result<{}, _>`, and it cannot be `async` or `const`.",
+                input_fn.sig.ident, invariant.state, struct_name, struct_name, struct_name
+            ),
+        ));
+    }
+
+    if let Some((_, object_type)) = &returned {
+        if calls_hooks {
+            let on_enter = Ident::new(&format!("OnEnter{}", struct_name), struct_name.span());
+            let on_exit = Ident::new(&format!("OnExit{}", struct_name), struct_name.span());
+            let old_type = quote!(#struct_name<#combined_generics>);
+
+            // with `on_err = keep`, the transition can fail with the object in its old states,
+            // so both of the hooks are only called for the object in `Ok`, `on_exit` then gets the object in its new states
+            let keep_on_err = transition.keep_on_err;
+            let exited_type = if keep_on_err {
+                quote!(#object_type)
+            } else {
+                old_type
+            };
+
+            for (from, to) in &changes {
+                // the hooks of the generic states have to be required
+                if generic_args.contains(from) {
+                    hook_where_clauses.push(quote!(#from: #on_exit<#exited_type>));
+                }
+                if generic_args.contains(to) {
+                    hook_where_clauses.push(quote!(#to: #on_enter<#object_type>));
+                }
+
+                if keep_on_err {
+                    after_body
+                        .push(quote!(<#from as #on_exit<#object_type>>::on_exit::<#to>(&object);));
+                } else {
+                    before_body
+                        .push(quote!(<#from as #on_exit<Self>>::on_exit::<#to>(&#self_token);));
+                }
+            }
+            for (from, to) in &changes {
+                after_body.push(
+                    quote!(<#to as #on_enter<#object_type>>::on_enter::<#from>(&mut object);),
+                );
+            }
+        }
+
+        for invariant in &entered_invariants {
+            let condition = replace_self(
+                invariant
+                    .condition
+                    .parse()
+                    .expect("the condition is a valid expression"),
+            );
+            let message = format!(
+                "Function `{}`: the invariant of `{}` is violated: `{}`",
+                input_fn.sig.ident, invariant.state, invariant.condition
+            );
+            after_body.push(quote!(::core::debug_assert!(#condition, "{}", #message);));
+        }
+    }
+
+    // the consumed object is not dropped in its state, so its drop check is disarmed (after the `OnExit` hooks that borrow it)
+    let forget_state = if disarms_self {
+        quote!(#self_token._state.disarm();)
+    } else {
+        quote! {}
+    };
+
+    let new_fn_body = match &returned {
+        Some((shape, _)) if !before_body.is_empty() || !after_body.is_empty() => {
+            let new_type = match &new_output {
+                ReturnType::Type(_, new_type) => new_type,
+                ReturnType::Default => unreachable!("the method returns the struct"),
+            };
+            // the checks only run on the object in its new state: `Some(object)` or `Ok(object)`
+            let checked = match shape {
+                ReturnShape::Object => quote! {
+                    let mut object = result;
+                    #(#after_body)*
+                    object
+                },
+                ReturnShape::Option => quote! {
+                    match result {
+                        ::core::option::Option::Some(mut object) => {
+                            #(#after_body)*
+                            ::core::option::Option::Some(object)
+                        }
+                        ::core::option::Option::None => ::core::option::Option::None,
+                    }
+                },
+                ReturnShape::Result => quote! {
+                    match result {
+                        ::core::result::Result::Ok(mut object) => {
+                            #(#after_body)*
+                            ::core::result::Result::Ok(object)
+                        }
+                        ::core::result::Result::Err(error) => ::core::result::Result::Err(error),
+                    }
+                },
+            };
+            // the body is wrapped in a closure, so that its `return`s (and `?`s) also go through the checks after it
+            quote! {
+                #(#before_body)*
+                #forget_state
+                #[allow(clippy::redundant_closure_call, unused_mut)]
+                let result: #new_type = (move || { #(#new_fn_body)* })();
+                #checked
+            }
+        }
+        _ => quote!(#forget_state #(#new_fn_body)*),
+    };
+    let new_where_clauses: Vec<TokenStream> = new_where_clauses
+        .into_iter()
+        .chain(hook_where_clauses)
+        .collect();
+
+    // Merge with the existing where clause, if any.
+    let merged_where_clause = if let Some(existing_where) = &impl_generics.where_clause {
+        quote! {
+            #existing_where #(#new_where_clauses),*
+        }
+    } else if !new_where_clauses.is_empty() {
+        quote! {
+            where #(#new_where_clauses),*
+        }
+    } else {
+        quote! {}
     };
 
     // construct the signature again
     let fn_sig = &mut input_fn.sig;
     fn_sig.output = new_output;
+    if disarms_self {
+        if let Some(FnArg::Receiver(receiver)) = fn_sig.inputs.first_mut() {
+            receiver.mutability = Some(<Token![mut]>::default());
+        }
+    }
 
     // extract visibility
     let fn_vis = &input_fn.vis;
@@ -126,61 +385,261 @@ pub fn generate_impl_block_for_method_based_on_require_args(
         #merged_where_clause
         {
             #(#other_attrs)*
+            #must_use
             #fn_vis #fn_sig {
-                #(#new_fn_body)*
+                #new_fn_body
             }
         }
     };
 
-    output
+    Ok(output)
+}
+
+/// `true` if the user already added `#[must_use]`
+pub fn has_must_use(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("must_use"))
+}
+
+/// `self` by value: `fn method(self, ...)`
+fn takes_self(input_fn: &ImplItemFn) -> bool {
+    matches!(
+        input_fn.sig.inputs.first(),
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_none() && receiver.colon_token.is_none()
+    )
+}
+
+/// How a method returns the object in its new state
+enum ReturnShape {
+    /// `Struct`
+    Object,
+    /// `Option<Struct>`
+    Option,
+    /// `Result<Struct, _>`
+    Result,
+}
+
+/// The body can be wrapped with the hooks and the invariant checks, if the method returns the object in one of the `ReturnShape`s
+///
+/// Returns the shape, and the type of the object: `Struct<State>`.
+fn returned_object<'a>(
+    input_fn: &ImplItemFn,
+    ty: &'a Type,
+    struct_name: &Ident,
+) -> Option<(ReturnShape, &'a Type)> {
+    let sig = &input_fn.sig;
+    if sig.asyncness.is_some() || sig.constness.is_some() {
+        return None;
+    }
+
+    let is_struct = |ty: &Type| {
+        matches!(
+            ty,
+            Type::Path(path) if path.qself.is_none()
+                && path.path.segments.last().is_some_and(|segment| segment.ident == *struct_name)
+        )
+    };
+    if is_struct(ty) {
+        return Some((ReturnShape::Object, ty));
+    }
+
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let shape = if segment.ident == "Option" {
+        ReturnShape::Option
+    } else if segment.ident == "Result" {
+        ReturnShape::Result
+    } else {
+        return None;
+    };
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(object_type) if is_struct(object_type) => Some((shape, object_type)),
+        _ => None,
+    }
 }
 
-fn modify_struct_in_expr(
-    expr: &Expr,
-    struct_name: &syn::Ident,
-    phantom_expr: TokenStream,
-) -> Option<Expr> {
-    match expr {
-        Expr::Struct(expr_struct) if expr_struct.path.is_ident(struct_name) => {
-            // Clone the struct fields and add the `_state` field
-            let mut new_fields = expr_struct.fields.clone();
-            new_fields.push(syn::FieldValue {
+/// `async fn method(...)`, or `fn method(...) -> impl Future<...>`, `Pin<Box<dyn Future<...>>>`...
+fn returns_future(sig: &Signature) -> bool {
+    sig.asyncness.is_some() || output_mentions(sig, "Future")
+}
+
+/// The return type has a path segment named `name` anywhere: `Option<Struct>`, `impl Future<Output = ...>`...
+fn output_mentions(sig: &Signature, name: &str) -> bool {
+    struct SegmentCheck<'a>(&'a str, bool);
+    impl<'ast> Visit<'ast> for SegmentCheck<'_> {
+        fn visit_path_segment(&mut self, segment: &'ast PathSegment) {
+            self.1 |= segment.ident == self.0;
+            syn::visit::visit_path_segment(self, segment);
+        }
+    }
+
+    let mut check = SegmentCheck(name, false);
+    check.visit_return_type(&sig.output);
+    check.1
+}
+
+/// `fn method(...) -> Struct`
+fn returns_struct(sig: &Signature, struct_name: &Ident) -> bool {
+    returns_path(sig, struct_name)
+}
+
+/// `fn method(...) -> Result<...>`
+fn returns_result(sig: &Signature) -> bool {
+    returns_path(sig, "Result")
+}
+
+/// The return type is a path ending with `name`
+fn returns_path<T: ?Sized>(sig: &Signature, name: &T) -> bool
+where
+    Ident: PartialEq<T>,
+{
+    match &sig.output {
+        ReturnType::Type(_, ty) => matches!(
+            &**ty,
+            Type::Path(path) if path.qself.is_none()
+                && path.path.segments.last().is_some_and(|segment| segment.ident == *name)
+        ),
+        ReturnType::Default => false,
+    }
+}
+
+/// The invariants are written with `self`, but they are checked on the returned object
+///
+/// Only `self` as a value is replaced, not as a path segment: `self::is_valid(&self.level)`.
+fn replace_self(tokens: TokenStream) -> TokenStream {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut replaced = Vec::new();
+    while let Some(token) = tokens.next() {
+        let is_path = matches!(
+            tokens.peek(),
+            Some(TokenTree::Punct(punct)) if punct.as_char() == ':' && punct.spacing() == Spacing::Joint
+        );
+        replaced.push(match token {
+            TokenTree::Ident(ident) if ident == "self" && !is_path => {
+                TokenTree::Ident(Ident::new("object", ident.span()))
+            }
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(group.delimiter(), replace_self(group.stream()));
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group)
+            }
+            other => other,
+        });
+    }
+    replaced.into_iter().collect()
+}
+
+/// Appends `_state` to the struct literals of the struct, wherever they are in the body:
+/// `Player { ... }`, `Ok(Player { ... })`, `async move { Player { ... } }`, `return Player { ... };`...
+struct StructLiteralRewriter<'a> {
+    struct_name: &'a Ident,
+    phantom_expr: &'a TokenStream,
+}
+
+impl VisitMut for StructLiteralRewriter<'_> {
+    fn visit_expr_struct_mut(&mut self, expr_struct: &mut ExprStruct) {
+        let has_state = expr_struct
+            .fields
+            .iter()
+            .any(|field| matches!(&field.member, Member::Named(name) if name == "_state"));
+        if expr_struct.path.is_ident(self.struct_name) && !has_state {
+            if !expr_struct.fields.empty_or_trailing() {
+                expr_struct.fields.push_punct(<Token![,]>::default());
+            }
+            expr_struct.fields.push(syn::FieldValue {
                 attrs: Vec::new(),
-                member: Member::Named(syn::Ident::new("_state", struct_name.span())),
+                member: Member::Named(Ident::new("_state", self.struct_name.span())),
                 colon_token: Some(<Token![:]>::default()),
-                expr: Expr::Verbatim(phantom_expr.clone()),
+                expr: Expr::Verbatim(self.phantom_expr.clone()),
             });
+        }
 
-            // Return a modified struct expression with the new fields
-            Some(Expr::Struct(ExprStruct {
-                fields: new_fields,
-                ..expr_struct.clone()
-            }))
+        // the fields might have struct literals as well
+        syn::visit_mut::visit_expr_struct_mut(self, expr_struct);
+    }
+}
+
+/// Arms the drop check again where the consumed `self` is passed on as a whole: `{ self._state.arm(); self }`,
+/// e.g. `return self`, `Some(self)` or `Self::commit(self)`.
+///
+/// The places that only use a part of `self` are left as they are: `self.field`, `self.method()`, `&self`,
+/// `..self`, `match self { ... }` and `let Struct { .. } = self`. The macros are not looked into.
+struct SelfMoveRewriter;
+
+impl SelfMoveRewriter {
+    /// Visits the expression, unless it is `self` itself
+    fn visit_place_mut(&mut self, expr: &mut Expr) {
+        if !is_self(expr) {
+            self.visit_expr_mut(expr);
         }
-        // If it's an expression like `Some(Player { ... })` or `Ok(Player { ... })`
-        Expr::Call(call_expr) => {
-            let mut new_args = vec![];
-            let mut modified = false;
-
-            for arg in &call_expr.args {
-                let phantom = phantom_expr.clone();
-                if let Some(modified_arg) = modify_struct_in_expr(arg, struct_name, phantom) {
-                    new_args.push(modified_arg);
-                    modified = true;
-                } else {
-                    new_args.push(arg.clone());
+    }
+}
+
+/// `self` as a value
+fn is_self(expr: &Expr) -> bool {
+    matches!(expr, Expr::Path(path) if path.qself.is_none() && path.path.is_ident("self"))
+}
+
+impl VisitMut for SelfMoveRewriter {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Path(path) if path.qself.is_none() && path.path.is_ident("self") => {
+                let self_token = &path.path.segments[0].ident;
+                *expr = Expr::Verbatim(quote!({ #self_token._state.arm(); #self_token }));
+            }
+            Expr::Field(field) => self.visit_place_mut(&mut field.base),
+            Expr::MethodCall(call) => {
+                self.visit_place_mut(&mut call.receiver);
+                for arg in &mut call.args {
+                    self.visit_expr_mut(arg);
+                }
+            }
+            Expr::Reference(reference) => self.visit_place_mut(&mut reference.expr),
+            Expr::Index(index) => {
+                self.visit_place_mut(&mut index.expr);
+                self.visit_expr_mut(&mut index.index);
+            }
+            Expr::Assign(assign) => {
+                self.visit_place_mut(&mut assign.left);
+                self.visit_expr_mut(&mut assign.right);
+            }
+            Expr::Struct(expr_struct) => {
+                for field in &mut expr_struct.fields {
+                    self.visit_expr_mut(&mut field.expr);
+                }
+                if let Some(rest) = &mut expr_struct.rest {
+                    self.visit_place_mut(rest);
+                }
+            }
+            Expr::Match(expr_match) => {
+                self.visit_place_mut(&mut expr_match.expr);
+                for arm in &mut expr_match.arms {
+                    self.visit_arm_mut(arm);
                 }
             }
+            Expr::Let(expr_let) => self.visit_place_mut(&mut expr_let.expr),
+            _ => syn::visit_mut::visit_expr_mut(self, expr),
+        }
+    }
 
-            if modified {
-                Some(Expr::Call(syn::ExprCall {
-                    args: new_args.into_iter().collect(),
-                    ..call_expr.clone()
-                }))
-            } else {
-                None
+    fn visit_local_mut(&mut self, local: &mut syn::Local) {
+        // `let object = self;` moves it, `let Struct { field, .. } = self;` only takes the fields
+        let binds_whole = matches!(&local.pat, Pat::Ident(ident) if ident.by_ref.is_none())
+            || matches!(&local.pat, Pat::Type(typed) if matches!(&*typed.pat, Pat::Ident(ident) if ident.by_ref.is_none()));
+        match &mut local.init {
+            Some(init) if !binds_whole && is_self(&init.expr) => {
+                if let Some((_, diverge)) = &mut init.diverge {
+                    self.visit_expr_mut(diverge);
+                }
             }
+            _ => syn::visit_mut::visit_local_mut(self, local),
         }
-        _ => None,
     }
+
+    // the nested items have their own `self`
+    fn visit_item_mut(&mut self, _item: &mut syn::Item) {}
 }
@@ -0,0 +1,346 @@
+// this file contains the logic that generates the runtime mirror of the state machine for `#[impl_state(runtime = true)]`:
+// - the `{StructName}Event` enum, with a variant for each transition method and its arguments,
+// - the `{StructName}TransitionError`, which gives back the object and the rejected event (and implements `std::error::Error`),
+// - and the `transition()` dispatcher on `Any{StructName}`, built from the `#[require]`/`#[switch_to]` macros.
+//
+// This is for the transitions that are only known at runtime (e.g. driven by network input).
+use proc_macro2::TokenStream;
+use quote::quote;
+use stringcase::pascal_case;
+use syn::{
+    visit::Visit, FnArg, GenericParam, Generics, Ident, PathArguments, ReturnType, Type,
+    TypeImplTrait, TypeReference, Visibility,
+};
+
+use crate::{
+    any_state::any_variant_ident, generic_args, impl_state::MethodTransition, info::TypeStateInfo,
+};
+
+/// A method that can be triggered by an event
+struct EventMethod<'a> {
+    transition: &'a MethodTransition,
+    variant: Ident,
+    arg_types: Vec<&'a Type>,
+}
+
+/// Detects the types that cannot be stored in an enum: elided lifetimes, `impl Trait` and `Self`
+struct StorableCheck(bool);
+
+impl<'ast> Visit<'ast> for StorableCheck {
+    fn visit_type_reference(&mut self, reference: &'ast TypeReference) {
+        if reference.lifetime.is_none() {
+            self.0 = false;
+        }
+        syn::visit::visit_type_reference(self, reference);
+    }
+
+    fn visit_lifetime(&mut self, lifetime: &'ast syn::Lifetime) {
+        if lifetime.ident == "_" {
+            self.0 = false;
+        }
+    }
+
+    fn visit_type_impl_trait(&mut self, _: &'ast TypeImplTrait) {
+        self.0 = false;
+    }
+
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        if ident == "Self" {
+            self.0 = false;
+        }
+    }
+}
+
+/// Only the methods that take `self` by value and return the struct itself can be triggered by an event,
+/// and their arguments have to be storable in the event.
+fn event_method<'a>(
+    transition: &'a MethodTransition,
+    struct_name: &Ident,
+) -> Option<EventMethod<'a>> {
+    let sig = &transition.sig;
+    if sig.asyncness.is_some() || sig.unsafety.is_some() || !sig.generics.params.is_empty() {
+        return None;
+    }
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next()? {
+        FnArg::Receiver(receiver)
+            if receiver.reference.is_none() && receiver.colon_token.is_none() => {}
+        _ => return None,
+    }
+
+    let mut arg_types = Vec::new();
+    for input in inputs {
+        let FnArg::Typed(arg) = input else {
+            return None;
+        };
+        let mut check = StorableCheck(true);
+        check.visit_type(&arg.ty);
+        if !check.0 {
+            return None;
+        }
+        arg_types.push(&*arg.ty);
+    }
+
+    let ReturnType::Type(_, return_type) = &sig.output else {
+        return None;
+    };
+    let Type::Path(return_type) = &**return_type else {
+        return None;
+    };
+    let returns_struct = return_type.qself.is_none()
+        && return_type.path.segments.last().is_some_and(|segment| {
+            segment.ident == *struct_name
+                || (segment.ident == "Self" && transition.switched_to.is_none())
+        });
+    if !returns_struct {
+        return None;
+    }
+
+    Some(EventMethod {
+        transition,
+        variant: Ident::new(&pascal_case(&sig.ident.to_string()), sig.ident.span()),
+        arg_types,
+    })
+}
+
+/// The state combination that the method switches into from `combination`, if it can be called there
+fn target_combination(
+    transition: &MethodTransition,
+    combination: &[String],
+) -> Option<Vec<String>> {
+    let mut bindings: Vec<(&Ident, &String)> = Vec::new();
+    for (required, state) in transition.required.iter().zip(combination) {
+        if transition.generics.contains(required) {
+            match bindings.iter().find(|(generic, _)| *generic == required) {
+                Some((_, bound)) if *bound != state => return None,
+                Some(_) => {}
+                None => bindings.push((required, state)),
+            }
+        } else if required != state {
+            return None;
+        }
+    }
+
+    let switched_to = transition
+        .switched_to
+        .as_ref()
+        .unwrap_or(&transition.required);
+    let target = switched_to
+        .iter()
+        .map(
+            |state| match bindings.iter().find(|(generic, _)| *generic == state) {
+                Some((_, bound)) => bound.to_string(),
+                None => state.to_string(),
+            },
+        )
+        .collect();
+    Some(target)
+}
+
+pub fn generate_runtime(
+    struct_name: &Ident,
+    struct_generics: &PathArguments,
+    impl_generics: &Generics,
+    transitions: &[MethodTransition],
+    info: &TypeStateInfo,
+) -> syn::Result<TokenStream> {
+    let Some(any_variants) = &info.any_variants else {
+        return Err(syn::Error::new(
+            struct_name.span(),
+            format!(
+                "`#[impl_state(runtime = true)]` requires `{}` to be declared with `#[type_state(any = true)]`.",
+                struct_name
+            ),
+        ));
+    };
+    let visibility: Visibility = syn::parse_str(&info.visibility).expect("visibility is valid");
+
+    let any_name = Ident::new(&format!("Any{}", struct_name), struct_name.span());
+    let event_name = Ident::new(&format!("{}Event", struct_name), struct_name.span());
+    let error_name = Ident::new(
+        &format!("{}TransitionError", struct_name),
+        struct_name.span(),
+    );
+    let struct_name_str = struct_name.to_string();
+
+    let (impl_generics_tokens, _, where_clause) = impl_generics.split_for_impl();
+    let event_args = generic_args(impl_generics);
+    let event_type = quote!(#event_name<#(#event_args),*>);
+    let any_type = quote!(#any_name #struct_generics);
+
+    let event_methods: Vec<EventMethod> = transitions
+        .iter()
+        .filter_map(|transition| event_method(transition, struct_name))
+        .collect();
+
+    let variant_ident = |states: &[String]| {
+        let states: Vec<Ident> = states
+            .iter()
+            .map(|state| Ident::new(state, struct_name.span()))
+            .collect();
+        any_variant_ident(&states)
+    };
+
+    // the event enum has the generics of the `impl` block, which might not be used by the arguments
+    let phantom_variant = if impl_generics.params.is_empty() {
+        quote! {}
+    } else {
+        let phantom_params: Vec<TokenStream> = impl_generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Lifetime(lifetime) => {
+                    let lifetime = &lifetime.lifetime;
+                    Some(quote!(&#lifetime ()))
+                }
+                GenericParam::Type(ty) => {
+                    let ident = &ty.ident;
+                    Some(quote!(#ident))
+                }
+                GenericParam::Const(_) => None,
+            })
+            .collect();
+        quote! {
+            #[doc(hidden)]
+            __Unreachable(
+                ::core::convert::Infallible,
+                ::core::marker::PhantomData<fn() -> (#(#phantom_params,)*)>,
+            ),
+        }
+    };
+    let phantom_arm = if impl_generics.params.is_empty() {
+        quote! {}
+    } else {
+        quote! { Self::__Unreachable(never, _) => match *never {}, }
+    };
+
+    let event_variants: Vec<TokenStream> = event_methods
+        .iter()
+        .map(|event| {
+            let variant = &event.variant;
+            let arg_types = &event.arg_types;
+            let doc = format!("Triggers `{}`", event.transition.sig.ident);
+            if arg_types.is_empty() {
+                quote! { #[doc = #doc] #variant, }
+            } else {
+                quote! { #[doc = #doc] #variant(#(#arg_types),*), }
+            }
+        })
+        .collect();
+
+    let event_names: Vec<TokenStream> = event_methods
+        .iter()
+        .map(|event| {
+            let variant = &event.variant;
+            let name = event.transition.sig.ident.to_string();
+            if event.arg_types.is_empty() {
+                quote! { Self::#variant => #name, }
+            } else {
+                quote! { Self::#variant(..) => #name, }
+            }
+        })
+        .collect();
+
+    // an arm for each (state combination, event) pair that is allowed
+    let mut arms: Vec<TokenStream> = Vec::new();
+    for event in &event_methods {
+        let variant = &event.variant;
+        let method = &event.transition.sig.ident;
+        let arg_names: Vec<Ident> = (0..event.arg_types.len())
+            .map(|i| Ident::new(&format!("arg{}", i), method.span()))
+            .collect();
+        let event_pattern = if arg_names.is_empty() {
+            quote!(#event_name::#variant)
+        } else {
+            quote!(#event_name::#variant(#(#arg_names),*))
+        };
+
+        for combination in any_variants {
+            let Some(target) = target_combination(event.transition, combination) else {
+                continue;
+            };
+            // the target state has to be representable by `Any{StructName}`
+            if !any_variants.contains(&target) {
+                continue;
+            }
+
+            let from_variant = variant_ident(combination);
+            arms.push(quote! {
+                (#any_name::#from_variant(value), #event_pattern) => {
+                    ::core::result::Result::Ok(value.#method(#(#arg_names),*).into())
+                }
+            });
+        }
+    }
+
+    let event_doc = format!(
+        "The transition methods of `{}`, as events that can be dispatched at runtime",
+        struct_name
+    );
+    let error_doc = format!(
+        "The event is not allowed in the current state of `{}`, gives back the object and the event",
+        struct_name
+    );
+
+    Ok(quote! {
+        #[doc = #event_doc]
+        #visibility enum #event_name #impl_generics #where_clause {
+            #(#event_variants)*
+            #phantom_variant
+        }
+
+        impl #impl_generics_tokens #event_type #where_clause {
+            /// Returns the name of the method that this event triggers
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#event_names)*
+                    #phantom_arm
+                }
+            }
+        }
+
+        #[doc = #error_doc]
+        #visibility struct #error_name #impl_generics #where_clause {
+            pub object: #any_type,
+            pub event: #event_type,
+        }
+
+        impl #impl_generics_tokens ::core::fmt::Debug for #error_name<#(#event_args),*> #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(stringify!(#error_name))
+                    .field("state", &self.object.state())
+                    .field("event", &self.event.name())
+                    .finish()
+            }
+        }
+
+        impl #impl_generics_tokens ::core::fmt::Display for #error_name<#(#event_args),*> #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                write!(
+                    f,
+                    "`{}` is not allowed in the `{:?}` state of `{}`",
+                    self.event.name(),
+                    self.object.state(),
+                    #struct_name_str
+                )
+            }
+        }
+
+        impl #impl_generics_tokens ::std::error::Error for #error_name<#(#event_args),*> #where_clause {}
+
+        impl #impl_generics_tokens #any_type #where_clause {
+            /// Dispatches the event to its method, if the method can be called in the current state
+            #[allow(unreachable_patterns, clippy::result_large_err)]
+            pub fn transition(
+                self,
+                event: #event_type,
+            ) -> ::core::result::Result<Self, #error_name<#(#event_args),*>> {
+                match (self, event) {
+                    #(#arms)*
+                    (object, event) => ::core::result::Result::Err(#error_name { object, event }),
+                }
+            }
+        }
+    })
+}
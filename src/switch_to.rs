@@ -1,78 +1,217 @@
 use syn::{
-    punctuated::Punctuated, visit_mut::VisitMut, Ident, PathArguments, ReturnType, Token, Type,
-    TypePath,
+    punctuated::Punctuated, spanned::Spanned, visit_mut::VisitMut, GenericArgument, Ident,
+    PathArguments, PathSegment, ReturnType, Token, Type, TypePath,
 };
 
 pub fn switch_to_inner(
     fn_output: &ReturnType,
     parsed_args: &Punctuated<Ident, Token![,]>,
+    on_err_args: Option<&Punctuated<Ident, Token![,]>>,
     struct_name: &Ident,
+    own_generics: usize,
     fn_name: &Ident,
-) -> ReturnType {
-    let generic_idents: Vec<syn::GenericArgument> = parsed_args
-        .iter()
-        .map(|i| {
-            syn::GenericArgument::Type(Type::Path(TypePath {
-                qself: None,
-                path: i.clone().into(),
-            }))
-        })
-        .collect();
-
+) -> syn::Result<ReturnType> {
     let original_return_type = match &fn_output {
         ReturnType::Type(_, ty) => &**ty,
-        _ => panic!(
-            "Function `{}`: Expected a return type with explicit type annotation (e.g., '-> Type'), but found none.",
-            fn_name
-        ),
+        _ => {
+            return Err(syn::Error::new(
+                fn_name.span(),
+                format!(
+                    "Function `{}`: Expected a return type with explicit type annotation (e.g., '-> Type'), but found none.",
+                    fn_name
+                ),
+            ))
+        }
     };
 
+    // the struct itself in the return type is what `#[switch_to]` is for
+    if let Type::Path(type_path) = original_return_type {
+        if let Some(segment) = type_path.path.segments.last() {
+            if segment.ident == *struct_name
+                && has_states(segment, own_generics, parsed_args.len(), fn_name)?
+            {
+                return Err(syn::Error::new(
+                    segment.span(),
+                    format!(
+                        "Function `{}`: the states of the returned `{}` are given by `#[switch_to]`, remove them from the return type.",
+                        fn_name, struct_name
+                    ),
+                ));
+            }
+        }
+    }
+
     let mut modified_return_type = original_return_type.clone();
 
     // Recursively modify the return type, using the struct_name to match
-    recursively_modify_return_type(
-        &mut modified_return_type,
-        generic_idents,
+    let mut injector = StateInjector {
         struct_name,
+        own_generics,
         fn_name,
-    );
+        generic_idents: to_generic_args(parsed_args),
+        on_err: on_err_args.map(to_generic_args),
+        found_err: false,
+        error: None,
+    };
+    injector.visit_type_mut(&mut modified_return_type);
+    if let Some(error) = injector.error {
+        return Err(error);
+    }
+
+    if on_err_args.is_some() && !injector.found_err {
+        return Err(syn::Error::new(
+            original_return_type.span(),
+            format!(
+                "Function `{}`: `on_err = keep` requires a `Result<_, _>` with an error type in the return type.",
+                fn_name
+            ),
+        ));
+    }
+
+    Ok(ReturnType::Type(
+        Default::default(),
+        Box::new(modified_return_type),
+    ))
+}
 
-    ReturnType::Type(Default::default(), Box::new(modified_return_type))
+fn to_generic_args(idents: &Punctuated<Ident, Token![,]>) -> Vec<GenericArgument> {
+    idents
+        .iter()
+        .map(|i| {
+            GenericArgument::Type(Type::Path(TypePath {
+                qself: None,
+                path: i.clone().into(),
+            }))
+        })
+        .collect()
 }
 
 // utilize `visit_type_mut` to handle all the variants of the return type in `syn`
 // otherwise, we would have to write a lot of match arms
-fn visit_type(ty: &mut Type, visitor: impl Fn(&mut TypePath)) {
-    struct TypeVisitor<F>(F);
-    impl<F: Fn(&mut TypePath)> VisitMut for TypeVisitor<F> {
-        fn visit_type_path_mut(&mut self, type_path: &mut TypePath) {
-            (self.0)(type_path);
+struct StateInjector<'a> {
+    struct_name: &'a Ident,
+    // the number of the struct's own type and const generics: `T` in `Slot<T>`
+    own_generics: usize,
+    fn_name: &'a Ident,
+    generic_idents: Vec<GenericArgument>,
+    // `on_err = keep`: the states of the struct in the error side of a `Result`
+    on_err: Option<Vec<GenericArgument>>,
+    found_err: bool,
+    // the visitor cannot return an error, so the first one is kept here
+    error: Option<syn::Error>,
+}
+
+impl StateInjector<'_> {
+    fn inject(&mut self, segment: &mut PathSegment) -> syn::Result<()> {
+        // the occurrences that already have their states are left alone:
+        // `(PlayerBuilder<LevelSet>, PlayerBuilder<Initial>)`
+        if segment.ident == *self.struct_name
+            && !has_states(
+                segment,
+                self.own_generics,
+                self.generic_idents.len(),
+                self.fn_name,
+            )?
+        {
+            modify_segment(segment, self.generic_idents.clone(), self.fn_name)?;
         }
+        Ok(())
     }
-    TypeVisitor(visitor).visit_type_mut(ty);
 }
 
-fn recursively_modify_return_type(
-    ty: &mut Type,
-    generic_idents: Vec<syn::GenericArgument>,
-    struct_name: &Ident,
-    fn_name: &Ident,
-) {
-    visit_type(ty, |type_path| {
+impl VisitMut for StateInjector<'_> {
+    fn visit_type_path_mut(&mut self, type_path: &mut TypePath) {
         // Check each segment in the path
         for segment in type_path.path.segments.iter_mut() {
-            if segment.ident == *struct_name {
-                modify_segment(segment, generic_idents.clone(), fn_name);
+            if let Err(error) = self.inject(segment) {
+                self.error.get_or_insert(error);
+            }
+        }
+
+        // `Result<Conn, (Conn, io::Error)>`: the error side gets the states of `on_err` instead
+        if let Some(on_err) = self.on_err.clone() {
+            if let Some((ok, err)) = result_types(type_path) {
+                let mut err_injector = StateInjector {
+                    struct_name: self.struct_name,
+                    own_generics: self.own_generics,
+                    fn_name: self.fn_name,
+                    generic_idents: on_err,
+                    on_err: None,
+                    found_err: false,
+                    error: None,
+                };
+                err_injector.visit_type_mut(err);
+                if let Some(error) = err_injector.error {
+                    self.error.get_or_insert(error);
+                }
+                self.visit_type_mut(ok);
+                self.found_err = true;
+                return;
             }
         }
+
+        // the struct might be nested in the generics: `Option<Player>`, `impl Future<Output = Player>`...
+        syn::visit_mut::visit_type_path_mut(self, type_path);
+    }
+}
+
+/// The `T` and `E` of `Result<T, E>`, the aliases with a fixed error type (`io::Result<T>`) are skipped
+fn result_types(type_path: &mut TypePath) -> Option<(&mut Type, &mut Type)> {
+    let segment = type_path.path.segments.last_mut()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &mut segment.arguments else {
+        return None;
+    };
+    let mut types = arguments.args.iter_mut().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
     });
+    match (types.next(), types.next()) {
+        (Some(ok), Some(err)) => Some((ok, err)),
+        _ => None,
+    }
+}
+
+/// Whether an occurrence of the struct is written with its states: `PlayerBuilder<LevelSet>` or `Slot<T, Full>`.
+/// The lifetimes are not counted, since they can be elided
+fn has_states(
+    segment: &PathSegment,
+    own_generics: usize,
+    slot_count: usize,
+    fn_name: &Ident,
+) -> syn::Result<bool> {
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return Ok(false);
+    };
+    let count = arguments
+        .args
+        .iter()
+        .filter(|arg| !matches!(arg, GenericArgument::Lifetime(_)))
+        .count();
+
+    if count > own_generics && count != own_generics + slot_count {
+        return Err(syn::Error::new(
+            segment.span(),
+            format!(
+                "Function `{}`: `{}` in the return type has {} state(s), but it has {} slot(s). Give all of them, or none: `{}<State1, State2, ...>`.",
+                fn_name,
+                segment.ident,
+                count - own_generics,
+                slot_count,
+                segment.ident
+            ),
+        ));
+    }
+    Ok(count > own_generics)
 }
 
 fn modify_segment(
     segment: &mut syn::PathSegment,
-    generic_idents: Vec<syn::GenericArgument>,
+    generic_idents: Vec<GenericArgument>,
     fn_name: &Ident,
-) {
+) -> syn::Result<()> {
     match &mut segment.arguments {
         PathArguments::AngleBracketed(arguments) => {
             arguments.args.extend(generic_idents);
@@ -86,9 +225,15 @@ fn modify_segment(
                     gt_token: Default::default(),
                 });
         }
-        _ => panic!(
-            "Function `{}`: Unsupported arguments in return type of the function.",
-            fn_name
-        ),
+        PathArguments::Parenthesized(arguments) => {
+            return Err(syn::Error::new(
+                arguments.span(),
+                format!(
+                    "Function `{}`: Unsupported arguments in return type of the function.",
+                    fn_name
+                ),
+            ))
+        }
     }
+    Ok(())
 }
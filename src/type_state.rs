@@ -1,9 +1,389 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use stringcase::snake_case;
-use syn::{parse_macro_input, Fields, Ident, ItemStruct};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote,
+    punctuated::Punctuated,
+    token, Attribute, Expr, Fields, Ident, ItemStruct, LitBool, Path, Token,
+};
 
-use crate::extract_idents_from_group;
+use crate::{
+    any_state::{generate_any_enum, generate_state_cell},
+    drop_check_ident, flag_state_ident, generic_args,
+    info::{InvariantInfo, SlotInfo, TransitionInfo, TypeStateInfo},
+    model::visibility_string,
+    require::has_must_use,
+    single_block_ident, slot_generic_ident, slot_sealer_ident,
+};
+
+/// Arguments of the `#[type_state]` macro: `states = (State1, State2, ...), slots = (State1, ...)`
+struct TypeStateArgs {
+    states: Vec<Ident>,
+    slots: Vec<Slot>,
+    transitions: Option<Vec<Transition>>,
+    /// `any = true`: generate the `Any{StructName}` enum
+    any: bool,
+    /// `cell = true`: generate `{StructName}Cell` and `{StructName}Guard`, for the transitions behind `&mut`
+    cell: bool,
+    /// `debug = true`: generate a `Debug` implementation that shows the states instead of `_state`
+    debug: bool,
+    /// `hooks = true`: generate the `OnEnter{StructName}`/`OnExit{StructName}` traits, which are called by the transitions
+    hooks: bool,
+    /// `hooks = (LevelSet, ...)`: only these states implement the hooks, the others get the empty ones
+    hooked_states: Option<Vec<Ident>>,
+    /// `state_names = true`: generate the `state_names(&self)` method
+    state_names: bool,
+    /// `parts = true`: generate `{StructName}Parts`, `into_parts()`, `from_parts()` and `assume_state()`
+    parts: bool,
+    /// `terminal = (Committed, ...)`: the states that the objects have to end in, checked when they are dropped
+    terminal: Vec<Ident>,
+}
+
+/// Parses a parenthesized list: `(Item1, Item2, ...)`
+fn parse_list<T: Parse>(input: ParseStream) -> syn::Result<Vec<T>> {
+    let content;
+    parenthesized!(content in input);
+    let parsed = Punctuated::<T, Token![,]>::parse_terminated(&content)?;
+    Ok(parsed.into_iter().collect())
+}
+
+/// A declared transition: `Initial -> RaceSet`, or `_ -> Initial` for a transition from any state
+struct Transition {
+    from: Option<Ident>,
+    to: Ident,
+}
+
+impl Parse for Transition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let from = if input.peek(Token![_]) {
+            input.parse::<Token![_]>()?;
+            None
+        } else {
+            Some(input.parse()?)
+        };
+        input.parse::<Token![->]>()?;
+        let to: Ident = input.parse()?;
+
+        Ok(Transition { from, to })
+    }
+}
+
+/// An invariant of a state, checked when an object enters it: `LevelSet => self.level.is_some()`
+struct Invariant {
+    state: Ident,
+    condition: Expr,
+}
+
+impl Parse for Invariant {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let state: Ident = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let condition: Expr = input.parse()?;
+
+        Ok(Invariant { state, condition })
+    }
+}
+
+/// A state slot, either positional: `Initial`, or named: `race: Initial`
+///
+/// Optionally followed by the states that are allowed in this slot: `race: Initial in (Initial, RaceSet)`
+struct Slot {
+    name: Option<Ident>,
+    default: Ident,
+    states: Option<Vec<Ident>>,
+    is_flag: bool,
+}
+
+impl Slot {
+    /// A flag is a named slot with two states: `race: RaceUnset in (RaceUnset, RaceSet)`
+    fn flag(name: Ident) -> Self {
+        let set = flag_state_ident(&name, true);
+        let unset = flag_state_ident(&name, false);
+        Slot {
+            name: Some(name),
+            default: unset.clone(),
+            states: Some(vec![unset, set]),
+            is_flag: true,
+        }
+    }
+}
+
+impl Parse for Slot {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = if input.peek2(Token![:]) {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![:]>()?;
+            Some(name)
+        } else {
+            None
+        };
+
+        let default: Ident = input.parse()?;
+
+        let states = if input.peek(Token![in]) {
+            input.parse::<Token![in]>()?;
+            let states: Vec<Ident> = parse_list(input)?;
+
+            if !states.contains(&default) {
+                return Err(syn::Error::new(
+                    default.span(),
+                    format!(
+                        "the default state `{}` is not one of the states of this slot",
+                        default
+                    ),
+                ));
+            }
+            Some(states)
+        } else {
+            None
+        };
+
+        Ok(Slot {
+            name,
+            default,
+            states,
+            is_flag: false,
+        })
+    }
+}
+
+impl Parse for TypeStateArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut states: Option<Vec<Ident>> = None;
+        let mut slots: Option<Vec<Slot>> = None;
+        let mut transitions: Option<Vec<Transition>> = None;
+        let mut flags: Vec<Ident> = Vec::new();
+        let mut any = false;
+        let mut cell = false;
+        let mut debug = false;
+        let mut hooks = false;
+        let mut hooked_states: Option<Vec<Ident>> = None;
+        let mut state_names = false;
+        let mut parts = false;
+        let mut terminal: Vec<Ident> = Vec::new();
+
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            match key.to_string().as_str() {
+                "states" => states = Some(parse_list(input)?),
+                "slots" => slots = Some(parse_list(input)?),
+                "transitions" => transitions = Some(parse_list(input)?),
+                "flags" => flags = parse_list(input)?,
+                "any" => any = input.parse::<LitBool>()?.value,
+                "cell" => cell = input.parse::<LitBool>()?.value,
+                "debug" => debug = input.parse::<LitBool>()?.value,
+                "hooks" if input.peek(token::Paren) => {
+                    hooks = true;
+                    hooked_states = Some(parse_list(input)?);
+                }
+                "hooks" => hooks = input.parse::<LitBool>()?.value,
+                "state_names" => state_names = input.parse::<LitBool>()?.value,
+                "parts" => parts = input.parse::<LitBool>()?.value,
+                "terminal" => terminal = parse_list(input)?,
+                _ => return Err(syn::Error::new(
+                    key.span(),
+                    "expected `states`, `slots`, `flags`, `transitions`, `any`, `cell`, `debug`, `hooks`, `state_names`, `parts` or `terminal`",
+                )),
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        // flags are appended to the slots as named slots
+        let mut slots: Vec<Slot> = match slots {
+            Some(slots) => slots,
+            None if !flags.is_empty() => Vec::new(),
+            None => return Err(input.error("expected a list of default slots")),
+        };
+        slots.extend(flags.into_iter().map(Slot::flag));
+
+        // `states` can be omitted if every slot lists its own states
+        let states: Vec<Ident> = match states {
+            Some(mut states) => {
+                for slot_state in slots
+                    .iter()
+                    .filter(|slot| !slot.is_flag)
+                    .flat_map(|slot| slot.states.iter().flatten())
+                {
+                    if !states.contains(slot_state) {
+                        return Err(syn::Error::new(
+                            slot_state.span(),
+                            format!("`{}` is not declared in `states`", slot_state),
+                        ));
+                    }
+                }
+
+                // the states of the flags are generated, they don't have to be declared
+                for flag_state in slots
+                    .iter()
+                    .filter(|slot| slot.is_flag)
+                    .flat_map(|slot| slot.states.iter().flatten())
+                {
+                    if !states.contains(flag_state) {
+                        states.push(flag_state.clone());
+                    }
+                }
+                states
+            }
+            None if slots.iter().all(|slot| slot.states.is_some()) => {
+                let mut states: Vec<Ident> = Vec::new();
+                for slot_state in slots.iter().flat_map(|slot| slot.states.iter().flatten()) {
+                    if !states.contains(slot_state) {
+                        states.push(slot_state.clone());
+                    }
+                }
+                states
+            }
+            None => return Err(input.error("expected a list of states")),
+        };
+
+        let named_slots = slots.iter().filter(|slot| slot.name.is_some()).count();
+        if named_slots != 0 && named_slots != slots.len() {
+            return Err(
+                input.error("expected either all of the slots to be named, or none of them")
+            );
+        }
+
+        for state in terminal.iter().chain(hooked_states.iter().flatten()) {
+            if !states.contains(state) {
+                return Err(syn::Error::new(
+                    state.span(),
+                    format!("`{}` is not declared in `states`", state),
+                ));
+            }
+        }
+
+        for transition in transitions.iter().flatten() {
+            for state in transition.from.iter().chain([&transition.to]) {
+                if !states.contains(state) {
+                    return Err(syn::Error::new(
+                        state.span(),
+                        format!("`{}` is not declared in `states`", state),
+                    ));
+                }
+            }
+        }
+
+        if cell && !any {
+            return Err(input.error("`cell = true` requires `any = true`"));
+        }
+
+        Ok(TypeStateArgs {
+            states,
+            slots,
+            transitions,
+            any,
+            cell,
+            debug,
+            hooks,
+            hooked_states,
+            state_names,
+            parts,
+            terminal,
+        })
+    }
+}
+
+/// The maximum number of the variants of `Any{StructName}`: they grow exponentially with the slots,
+/// and each of them generates a `From` and a `try_into_*`, which slows down the build
+const MAX_ANY_VARIANTS: usize = 256;
+
+/// The derives of the struct: `#[derive(Clone, serde::Serialize)]`
+fn derives(attrs: &[Attribute]) -> Vec<Path> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                .ok()
+        })
+        .flatten()
+        .collect()
+}
+
+/// The serde derives of the struct: `#[derive(Serialize, serde::Deserialize)]`
+fn serde_derives(attrs: &[Attribute]) -> Vec<Path> {
+    derives(attrs)
+        .into_iter()
+        .filter(|path| {
+            path.segments.last().is_some_and(|segment| {
+                segment.ident == "Serialize" || segment.ident == "Deserialize"
+            })
+        })
+        .collect()
+}
+
+/// The state combinations that the struct can be in, starting from the default states of the slots
+///
+/// A slot can be in any of its states (or any of the `states`, if the slot doesn't list its own),
+/// unless `transitions` are declared, in which case only the states reachable from its default state are kept.
+///
+/// Returns the number of the combinations as the error if there are more than `MAX_ANY_VARIANTS`.
+fn reachable_state_combinations(
+    slots: &[Slot],
+    states: &[Ident],
+    transitions: Option<&[Transition]>,
+) -> Result<Vec<Vec<Ident>>, usize> {
+    let reachable_per_slot: Vec<Vec<Ident>> = slots
+        .iter()
+        .map(|slot| {
+            let slot_states = slot.states.as_deref().unwrap_or(states);
+            let Some(transitions) = transitions else {
+                return slot_states.to_vec();
+            };
+
+            let mut reachable = vec![slot.default.clone()];
+            let mut i = 0;
+            while i < reachable.len() {
+                for transition in transitions {
+                    let from_matches = match &transition.from {
+                        Some(from) => from == &reachable[i],
+                        None => true,
+                    };
+                    if from_matches
+                        && slot_states.contains(&transition.to)
+                        && !reachable.contains(&transition.to)
+                    {
+                        reachable.push(transition.to.clone());
+                    }
+                }
+                i += 1;
+            }
+            reachable
+        })
+        .collect();
+
+    let count = reachable_per_slot
+        .iter()
+        .fold(1usize, |count, slot_states| {
+            count.saturating_mul(slot_states.len())
+        });
+    if count > MAX_ANY_VARIANTS {
+        return Err(count);
+    }
+
+    // cartesian product of the slots
+    Ok(reachable_per_slot
+        .into_iter()
+        .fold(vec![Vec::new()], |combinations, slot_states| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    slot_states.iter().map(move |state| {
+                        let mut combination = combination.clone();
+                        combination.push(state.clone());
+                        combination
+                    })
+                })
+                .collect()
+        }))
+}
 
 pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
     // Parse the input struct
@@ -13,22 +393,107 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
     let visibility = &input_struct.vis;
 
     // Parse arguments (states and slots)
-    /*
-    Indices:
-    ---
-    0. `states`
-    1. `=`
-    2. `(State1, State2, State3)`
-    3. `,`
-    4. `slots`
-    5. `=`
-    6. `(State1, State1)`
-     */
-    let input_args: Vec<_> = args.into_iter().collect();
-    let states: Vec<Ident> = extract_idents_from_group(&input_args[2], "expected a list of states");
-
-    let default_slots: Vec<Ident> =
-        extract_idents_from_group(&input_args[6], "expected a list of default slots");
+    let TypeStateArgs {
+        states,
+        slots,
+        transitions,
+        any,
+        cell,
+        debug,
+        hooks,
+        hooked_states,
+        state_names,
+        parts,
+        terminal,
+    } = parse_macro_input!(args as TypeStateArgs);
+
+    // The invariants of the states: `#[invariant(LevelSet => self.level.is_some())]`
+    let mut invariants = Vec::new();
+    for attr in input_struct
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("invariant"))
+    {
+        match attr.parse_args::<Invariant>() {
+            Ok(invariant) if !states.contains(&invariant.state) => {
+                return syn::Error::new_spanned(
+                    &invariant.state,
+                    format!("`{}` is not declared in `states`", invariant.state),
+                )
+                .to_compile_error()
+                .into();
+            }
+            Ok(invariant) => invariants.push(invariant),
+            Err(error) => return error.to_compile_error().into(),
+        }
+    }
+
+    // the state combinations of the variants of `Any{StructName}`, if requested
+    let any_variants = match any
+        .then(|| reachable_state_combinations(&slots, &states, transitions.as_deref()))
+        .transpose()
+    {
+        Ok(any_variants) => any_variants,
+        Err(count) => {
+            return syn::Error::new_spanned(
+                struct_name,
+                format!(
+                    "`any = true` generates a variant of `Any{}` for each state combination, but there are {} of them (at most {} are supported). \
+                     Restrict the states of the slots (`race: Initial in (Initial, RaceSet)`), or declare the `transitions`, so that only the reachable states are kept.",
+                    struct_name, count, MAX_ANY_VARIANTS
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    // Describe the slots and the transitions, so that `#[impl_state]` can
+    // resolve the named arguments of `#[require]` and `#[switch_to]`, and validate the transitions
+    let info = TypeStateInfo {
+        states: states.iter().map(|state| state.to_string()).collect(),
+        slots: slots
+            .iter()
+            .map(|slot| SlotInfo {
+                name: slot.name.as_ref().map(|name| name.to_string()),
+                default: slot.default.to_string(),
+                is_flag: slot.is_flag,
+                states: slot
+                    .states
+                    .as_ref()
+                    .map(|states| states.iter().map(|state| state.to_string()).collect()),
+            })
+            .collect(),
+        transitions: transitions.as_ref().map(|transitions| {
+            transitions
+                .iter()
+                .map(|transition| TransitionInfo {
+                    from: transition.from.as_ref().map(|from| from.to_string()),
+                    to: transition.to.to_string(),
+                })
+                .collect()
+        }),
+        visibility: visibility_string(visibility),
+        any_variants: any_variants.as_ref().map(|combinations| {
+            combinations
+                .iter()
+                .map(|states| states.iter().map(|state| state.to_string()).collect())
+                .collect()
+        }),
+        hooks,
+        terminal: terminal.iter().map(|state| state.to_string()).collect(),
+        invariants: invariants
+            .iter()
+            .map(|invariant| {
+                let condition = &invariant.condition;
+                InvariantInfo {
+                    state: invariant.state.to_string(),
+                    condition: quote!(#condition).to_string(),
+                }
+            })
+            .collect(),
+        methods: Vec::new(),
+    };
 
     // Generate the marker structs and sealing traits
     let sealer_trait_name = Ident::new(&format!("Sealer{}", struct_name), struct_name.span());
@@ -41,7 +506,9 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
         .iter()
         .map(|state| {
             let marker_name = Ident::new(&format!("{}", state), state.span());
+            // the common traits are derived, so that the derives of the struct are not blocked by the state generics
             quote! {
+                #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
                 pub struct #marker_name;
             }
         })
@@ -67,6 +534,119 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
         })
         .collect();
 
+    // Generate the trait that reveals the names of the states at runtime: `<RaceSet as StateNamePlayerBuilder>::NAME`
+    let state_name_trait_name =
+        Ident::new(&format!("StateName{}", struct_name), struct_name.span());
+
+    let state_name_impls: Vec<_> = states
+        .iter()
+        .map(|state| {
+            let marker_name = Ident::new(&format!("{}", state), state.span());
+            let name = state.to_string();
+            quote! {
+                impl #state_name_trait_name for #marker_name {
+                    const NAME: &'static str = #name;
+                }
+            }
+        })
+        .collect();
+
+    // Generate the hooks, if requested: they are implemented by the users for each state (or each of the listed states),
+    // and called by the transitions (`#[switch_to]`) with the typed object
+    let on_enter_trait_name = Ident::new(&format!("OnEnter{}", struct_name), struct_name.span());
+    let on_exit_trait_name = Ident::new(&format!("OnExit{}", struct_name), struct_name.span());
+    let hook_traits = if hooks {
+        let on_enter_doc = format!(
+            "Called when `{}` enters this state, `Object` is the typed object in its new state",
+            struct_name
+        );
+        let on_exit_doc = format!(
+            "Called when `{}` leaves this state, `Object` is the typed object in its old state",
+            struct_name
+        );
+        // with `hooks = (LevelSet, ...)`, the other states get the empty hooks
+        let unhooked_states = states.iter().filter(|state| {
+            hooked_states
+                .as_ref()
+                .is_some_and(|hooked_states| !hooked_states.contains(state))
+        });
+        quote! {
+            #[doc = #on_enter_doc]
+            pub trait #on_enter_trait_name<Object> {
+                /// `From` is the state that the object comes from
+                #[inline(always)]
+                fn on_enter<From: #sealer_trait_name>(_object: &mut Object) {}
+            }
+
+            #[doc = #on_exit_doc]
+            pub trait #on_exit_trait_name<Object> {
+                /// `To` is the state that the object goes to
+                #[inline(always)]
+                fn on_exit<To: #sealer_trait_name>(_object: &Object) {}
+            }
+
+            #(
+                impl<Object> #on_enter_trait_name<Object> for #unhooked_states {}
+                impl<Object> #on_exit_trait_name<Object> for #unhooked_states {}
+            )*
+        }
+    } else {
+        quote! {}
+    };
+
+    // Generate a sealing trait for each slot that lists its own states,
+    // so that a state cannot be put into a slot that it doesn't belong to
+    let slot_sealer_names: Vec<Option<Ident>> = slots
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            slot.states.as_ref().map(|_| {
+                let slot_name = slot.name.as_ref().map(|name| name.to_string());
+                slot_sealer_ident(struct_name, i, slot_name.as_deref())
+            })
+        })
+        .collect();
+
+    let slot_sealers: Vec<_> = slots
+        .iter()
+        .zip(&slot_sealer_names)
+        .filter_map(|(slot, slot_sealer_name)| {
+            let slot_sealer_name = slot_sealer_name.as_ref()?;
+            let slot_states = slot.states.as_ref()?;
+            Some(quote! {
+                #[allow(non_camel_case_types)]
+                pub trait #slot_sealer_name: #sealer_trait_name {}
+
+                #(impl #slot_sealer_name for #slot_states {})*
+            })
+        })
+        .collect();
+
+    // With the `serde` feature, the serde derives of the struct skip `_state`,
+    // and they are also applied to `Any{StructName}`, which stores the state as a tag
+    let serde_derives = if cfg!(feature = "serde") {
+        serde_derives(&input_struct.attrs)
+    } else {
+        Vec::new()
+    };
+    let serde_skip = if serde_derives.is_empty() {
+        quote! {}
+    } else {
+        quote! { #[serde(skip)] }
+    };
+
+    // Generate the type-erased enum, if requested
+    let any_enum = match &any_variants {
+        Some(state_combinations) => generate_any_enum(
+            struct_name,
+            visibility,
+            generics,
+            state_combinations,
+            &serde_derives,
+        ),
+        None => quote! {},
+    };
+
     // Extract fields from the struct
     // we cannot use `input_struct.fields` directly because
     // quote! treats the Fields reference as a block expression,
@@ -78,18 +658,19 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
         Fields::Unit => panic!("Expected a struct with fields."),
     };
 
-    // Generate state generics: `struct StructName<PlayerState1, PlayerState2, ...>`
-    let state_idents: Vec<_> = (0..default_slots.len())
-        .map(|i| {
-            Ident::new(
-                &format!("{}State{}", struct_name, i + 1),
-                struct_name.span(),
-            )
+    // Generate state generics: `struct StructName<PlayerState1, PlayerState2, ...>`,
+    // or `struct StructName<PlayerStateRace, PlayerStateLevel, ...>` for named slots
+    let state_idents: Vec<_> = slots
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            let slot_name = slot.name.as_ref().map(|name| name.to_string());
+            slot_generic_ident(struct_name, i, slot_name.as_deref())
         })
         .collect();
 
     // Construct the new generics by merging original generics with default states
-    let default_generics = default_slots.iter().collect::<Vec<_>>();
+    let default_generics = slots.iter().map(|slot| &slot.default).collect::<Vec<_>>();
     let combined_generics = if generics.params.is_empty() {
         quote! { #(#state_idents = #default_generics),* }
     } else {
@@ -97,12 +678,41 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
         quote! { #(#original_generics),*, #(#state_idents = #default_generics),* }
     };
 
+    // the bound of each state generic: the slot's sealer trait if it lists its own states
+    let state_bounds: Vec<_> = slot_sealer_names
+        .iter()
+        .map(|slot_sealer_name| slot_sealer_name.as_ref().unwrap_or(&sealer_trait_name))
+        .collect();
+
     // create a new where clause for the new generics (states)
     let new_where_clause: Vec<_> = state_idents
         .iter()
-        .map(|state| quote!(#state: #sealer_trait_name))
+        .zip(&state_bounds)
+        .map(|(state, bound)| quote!(#state: #bound))
         .collect();
 
+    // The generics of the `impl` blocks generated for the struct: the original generics + the bounded state generics
+    let mut state_generics = generics.clone();
+    for (state, bound) in state_idents.iter().zip(&state_bounds) {
+        state_generics.params.push(parse_quote!(#state: #bound));
+    }
+    let (impl_generics, ty_generics, where_clause) = state_generics.split_for_impl();
+
+    let slot_count = state_idents.len();
+
+    // Generate the cell for the transitions behind `&mut`, if requested
+    let state_cell = match &any_variants {
+        Some(state_combinations) if cell => generate_state_cell(
+            struct_name,
+            visibility,
+            generics,
+            &state_idents,
+            &state_bounds,
+            state_combinations,
+        ),
+        _ => quote! {},
+    };
+
     // Merge the where clauses if there is an existing one
     let merged_where_clause = if let Some(existing_where) = &generics.where_clause {
         quote! { #existing_where #(#new_where_clause),* }
@@ -120,20 +730,354 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
         .map(|ident| quote!(::core::marker::PhantomData<fn() -> #ident>))
         .collect::<Vec<_>>();
 
-    // Get the struct's attributes (other macros) excluding the #[type_state] macro
+    // Generate the parts of the struct: the same fields without `_state`,
+    // so that the objects can be taken apart and rebuilt in a state without depending on the layout of `_state`
+    let parts_name = Ident::new(&format!("{}Parts", struct_name), struct_name.span());
+    let field_names: Vec<_> = struct_fields.iter().map(|field| &field.ident).collect();
+    let parts_fields: Vec<_> = struct_fields
+        .iter()
+        .map(|field| {
+            let mut field = field.clone();
+            // only the docs and the `cfg`s make sense on the parts, the others might belong to a derive of the struct
+            field
+                .attrs
+                .retain(|attr| attr.path().is_ident("doc") || attr.path().is_ident("cfg"));
+            field
+        })
+        .collect();
+    let (_, original_ty_generics, original_where_clause) = generics.split_for_impl();
+    let original_args = generic_args(generics);
+    let parts_doc = format!(
+        "The fields of `{}` without its state, see `{}::into_parts()` and `{}::from_parts()`",
+        struct_name, struct_name, struct_name
+    );
+
+    // `assume_state::<AssumedPlayerBuilderState1, ...>()`
+    let assumed_idents: Vec<_> = state_idents
+        .iter()
+        .map(|ident| Ident::new(&format!("Assumed{}", ident), ident.span()))
+        .collect();
+    let state_markers = state_idents
+        .iter()
+        .map(|_| quote!(::core::marker::PhantomData))
+        .collect::<Vec<_>>();
+
+    // Generate the drop check, if there are terminal states:
+    // `_state` becomes a zero-sized guard, which panics (in debug builds) if it is dropped in a non-terminal state.
+    // The guard is on `_state` and not on the struct, so that the fields can still be moved out of the objects,
+    // and the methods that take `self` disarm the guard of the consumed object (and arm it again if they pass `self` on as a whole).
+    let drop_check_name = drop_check_ident(struct_name);
+    let (state_type, state_value, consume_state, drop_check) = if terminal.is_empty() {
+        (
+            quote!((#(#phantom_fields),*)),
+            quote!((#(#state_markers),*)),
+            quote!(let this = self;),
+            quote! {},
+        )
+    } else {
+        if let Some(derived) = derives(&input_struct.attrs).into_iter().find(|path| {
+            path.segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Copy")
+        }) {
+            return syn::Error::new_spanned(
+                derived,
+                "the objects with `terminal` states have to be consumed, so they cannot be `Copy`",
+            )
+            .to_compile_error()
+            .into();
+        }
+
+        let struct_name_str = struct_name.to_string();
+        let terminal_strs: Vec<String> = terminal.iter().map(|state| state.to_string()).collect();
+        let terminal_list = format!("`{}`", terminal_strs.join("`, `"));
+        let drop_check_doc = format!(
+            "Checks that `{}` is dropped in one of its terminal states: {}",
+            struct_name, terminal_list
+        );
+        let state_bound_params: Vec<_> = state_idents
+            .iter()
+            .zip(&state_bounds)
+            .map(|(state, bound)| quote!(#state: #bound))
+            .collect();
+
+        let drop_check_name_str = drop_check_name.to_string();
+        let drop_check_type = quote!(#drop_check_name<#(#state_idents),*>);
+
+        // the guard is the same for all of the objects in a state, so it doesn't take part in the comparisons,
+        // and the clones are armed (they have to be consumed as well)
+        let drop_check = quote! {
+            #[doc = #drop_check_doc]
+            #[doc(hidden)]
+            #visibility struct #drop_check_name<#(#state_bound_params),*> {
+                armed: bool,
+                states: ::core::marker::PhantomData<fn() -> (#(#state_idents,)*)>,
+            }
+
+            impl<#(#state_bound_params),*> #drop_check_type {
+                /// An armed guard, for a new object
+                pub const fn new() -> Self {
+                    Self {
+                        armed: true,
+                        states: ::core::marker::PhantomData,
+                    }
+                }
+
+                /// The object is consumed, so it is not dropped in its state
+                pub fn disarm(&mut self) {
+                    self.armed = false;
+                }
+
+                /// The object is passed on as a whole, so it has to be consumed again
+                pub fn arm(&mut self) {
+                    self.armed = true;
+                }
+            }
+
+            impl<#(#state_bound_params),*> ::core::clone::Clone for #drop_check_type {
+                fn clone(&self) -> Self {
+                    Self::new()
+                }
+            }
+
+            impl<#(#state_bound_params),*> ::core::default::Default for #drop_check_type {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+
+            impl<#(#state_bound_params),*> ::core::fmt::Debug for #drop_check_type {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(#drop_check_name_str)
+                }
+            }
+
+            impl<#(#state_bound_params),*> ::core::cmp::PartialEq for #drop_check_type {
+                fn eq(&self, _other: &Self) -> bool {
+                    true
+                }
+            }
+
+            impl<#(#state_bound_params),*> ::core::cmp::Eq for #drop_check_type {}
+
+            impl<#(#state_bound_params),*> ::core::hash::Hash for #drop_check_type {
+                fn hash<H: ::core::hash::Hasher>(&self, _state: &mut H) {}
+            }
+
+            impl<#(#state_bound_params),*> ::core::ops::Drop for #drop_check_type {
+                fn drop(&mut self) {
+                    const TERMINAL: &[&str] = &[#(#terminal_strs),*];
+                    let states = [#(<#state_idents as #state_name_trait_name>::NAME),*];
+                    // a panic while panicking would abort, the object is probably dropped because of the first panic
+                    if cfg!(debug_assertions)
+                        && self.armed
+                        && !::std::thread::panicking()
+                        && !states.iter().any(|state| TERMINAL.contains(state))
+                    {
+                        panic!(
+                            "`{}` is dropped in the non-terminal state {:?}, it has to end in one of {}",
+                            #struct_name_str, states, #terminal_list
+                        );
+                    }
+                }
+            }
+        };
+
+        (
+            drop_check_type,
+            quote!(#drop_check_name::new()),
+            quote!(let mut this = self; this._state.disarm();),
+            drop_check,
+        )
+    };
+
+    // Generate the `Debug` implementation, if requested:
+    // `PlayerBuilder { race: Some(Orc), level: None, state: "RaceSet" }`, or `state: ["RaceSet", "LevelUnset"]` for multiple slots
+    let debug_impl = if debug {
+        if let Some(derived) = derives(&input_struct.attrs).into_iter().find(|path| {
+            path.segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Debug")
+        }) {
+            return syn::Error::new_spanned(
+                derived,
+                "`debug = true` generates the `Debug` implementation, remove `Debug` from the derives",
+            )
+            .to_compile_error()
+            .into();
+        }
+
+        let struct_name_str = struct_name.to_string();
+        let field_name_strs = field_names
+            .iter()
+            .map(|name| name.as_ref().map(|name| name.to_string()));
+        let state_value = if slot_count == 1 {
+            let state_ident = &state_idents[0];
+            quote!(&<#state_ident as #state_name_trait_name>::NAME)
+        } else {
+            quote!(&[#(<#state_idents as #state_name_trait_name>::NAME),*])
+        };
+
+        // like the standard derive, the type parameters are required to implement `Debug`
+        let mut debug_generics = state_generics.clone();
+        for param in generics.type_params() {
+            let ident = &param.ident;
+            debug_generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#ident: ::core::fmt::Debug));
+        }
+        let (debug_impl_generics, _, debug_where_clause) = debug_generics.split_for_impl();
+
+        quote! {
+            impl #debug_impl_generics ::core::fmt::Debug for #struct_name #ty_generics #debug_where_clause {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.debug_struct(#struct_name_str)
+                        #(.field(#field_name_strs, &self.#field_names))*
+                        .field("state", #state_value)
+                        .finish()
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Get the struct's attributes (other macros) excluding the #[type_state] macro and the invariants
     let attrs: Vec<_> = input_struct
         .attrs
         .iter()
-        .filter(|attr| !attr.path().is_ident("type_state"))
+        .filter(|attr| !attr.path().is_ident("type_state") && !attr.path().is_ident("invariant"))
         .collect();
 
+    // the objects are moved from state to state, so throwing one away is most likely a mistake
+    let must_use = if has_must_use(&input_struct.attrs) {
+        quote! {}
+    } else {
+        quote!(#[must_use = "state transition result must be used"])
+    };
+
+    // Each `#[impl_state]` block implements this trait: a block that needs all of the methods of the struct (e.g. `runtime = true` or `dot = true`)
+    // implements it for every `Block`, so that it conflicts with any other block
+    let single_block_name = single_block_ident(struct_name);
+    let single_block_doc = format!(
+        "Implemented by each `#[impl_state]` block of `{}`, a conflicting implementation means that a block with `runtime`, `dot`, `mermaid`, `json` or `reachability` is not the only one",
+        struct_name
+    );
+    let single_block_trait = quote! {
+        #[doc(hidden)]
+        #[doc = #single_block_doc]
+        pub trait #single_block_name<Block> {}
+    };
+
+    // `#[impl_state]` expands into a call of this macro (by the name of the struct), which passes the description back to it
+    let info_macro_name = Ident::new(
+        &format!("__state_shift_{}", struct_name),
+        struct_name.span(),
+    );
+    let info = info.encode();
+    let info_macro = quote! {
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        macro_rules! #info_macro_name {
+            ($($input:tt)*) => {
+                ::state_shift::__impl_state_with_info! { { #info } $($input)* }
+            };
+        }
+
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #info_macro_name as #struct_name;
+    };
+
+    // `parts = true`: the objects can be taken apart and rebuilt in a state, e.g. after loading them from disk
+    let parts_impl = if parts {
+        quote! {
+            #[doc = #parts_doc]
+            #visibility struct #parts_name #generics #original_where_clause {
+                #(#parts_fields),*
+            }
+
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                /// Takes the object apart, the state is dropped
+                pub fn into_parts(self) -> #parts_name #original_ty_generics {
+                    #consume_state
+                    #parts_name {
+                        #(#field_names: this.#field_names),*
+                    }
+                }
+
+                /// Rebuilds the object in the state of `Self` from its parts (e.g. after loading it from disk)
+                ///
+                /// # Safety
+                ///
+                /// The caller must ensure that the parts are valid for the state of `Self`,
+                /// the methods of this state rely on it (e.g. a field that is set in this state).
+                pub unsafe fn from_parts(parts: #parts_name #original_ty_generics) -> Self {
+                    #struct_name {
+                        #(#field_names: parts.#field_names,)*
+                        _state: #state_value,
+                    }
+                }
+
+                /// Moves the object into the given state, without any transition method
+                ///
+                /// # Safety
+                ///
+                /// The caller must ensure that the object is valid for the given state,
+                /// the methods of this state rely on it (e.g. a field that is set in this state).
+                #[allow(clippy::type_complexity)]
+                pub unsafe fn assume_state<#(#assumed_idents: #state_bounds),*>(
+                    self,
+                ) -> #struct_name<#(#original_args,)* #(#assumed_idents),*> {
+                    #consume_state
+                    #struct_name {
+                        #(#field_names: this.#field_names,)*
+                        _state: #state_value,
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // `state_names = true`: the names of the current states, e.g. for logging
+    let state_names_impl = if state_names {
+        quote! {
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                /// Returns the names of the current states of the slots, without any runtime cost
+                pub fn state_names(&self) -> [&'static str; #slot_count] {
+                    [#(<#state_idents as #state_name_trait_name>::NAME),*]
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     // Generate the final output
     let output = quote! {
         mod #sealed_mod_name {
             pub trait Sealed {}
         }
 
-        pub trait #sealer_trait_name: #sealed_mod_name::Sealed {}
+        pub trait #state_name_trait_name {
+            const NAME: &'static str;
+        }
+
+        // the derived traits of the markers are required here, so that they are also available for the generic states
+        pub trait #sealer_trait_name:
+            #sealed_mod_name::Sealed
+            + #state_name_trait_name
+            + ::core::clone::Clone
+            + ::core::marker::Copy
+            + ::core::fmt::Debug
+            + ::core::cmp::PartialEq
+            + ::core::cmp::Eq
+            + ::core::hash::Hash
+            + ::core::default::Default
+        {}
 
         #(#markers)*
 
@@ -141,14 +1085,38 @@ pub fn type_state_inner(args: TokenStream, input: TokenStream) -> TokenStream {
 
         #(#trait_impls)*
 
+        #(#state_name_impls)*
+
+        #(#slot_sealers)*
+
+        #hook_traits
+
+        #single_block_trait
+
+        #drop_check
+
         #(#attrs)*
+        #must_use
         #[allow(clippy::type_complexity)]
         #visibility struct #struct_name<#combined_generics>
         #merged_where_clause
         {
             #struct_fields
-            _state: (#(#phantom_fields),*),
+            #serde_skip
+            _state: #state_type,
         }
+
+        #state_names_impl
+
+        #parts_impl
+
+        #debug_impl
+
+        #any_enum
+
+        #state_cell
+
+        #info_macro
     };
 
     output.into()
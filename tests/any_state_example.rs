@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Connected, Authenticated, Closed, Unused),
+    slots = (Connected),
+    transitions = (Connected -> Authenticated, _ -> Closed),
+    any = true // generates `AnySession`, with a variant for each reachable state (`Unused` is not reachable)
+)]
+struct Session<'a> {
+    peer: &'a str,
+    user: Option<String>,
+}
+
+#[impl_state]
+impl<'a> Session<'a> {
+    #[require(Connected)]
+    fn new(peer: &'a str) -> Session<'a> {
+        Session { peer, user: None }
+    }
+
+    #[require(Connected)]
+    #[switch_to(Authenticated)]
+    fn authenticate(self, user: &str) -> Session<'a> {
+        Session {
+            peer: self.peer,
+            user: Some(user.to_string()),
+        }
+    }
+
+    #[require(A)]
+    #[switch_to(Closed)]
+    fn close(self) -> Session<'a> {
+        Session {
+            peer: self.peer,
+            user: self.user,
+        }
+    }
+
+    #[require(Authenticated)]
+    fn user(&self) -> &str {
+        self.user
+            .as_deref()
+            .expect("type safety ensures this is set")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_in_different_states_can_be_stored_together() {
+        let mut sessions: HashMap<u32, AnySession> = HashMap::new();
+
+        sessions.insert(1, Session::new("10.0.0.1").into());
+        sessions.insert(2, Session::new("10.0.0.2").authenticate("alice").into());
+        sessions.insert(3, Session::new("10.0.0.3").close().into());
+
+        assert_eq!(sessions[&1].state_names(), ["Connected"]);
+        assert_eq!(sessions[&2].state_names(), ["Authenticated"]);
+        assert_eq!(sessions[&3].state_names(), ["Closed"]);
+
+        // back to the typed world
+        let session = sessions.remove(&2).unwrap();
+        let session = session
+            .try_into_authenticated()
+            .unwrap_or_else(|_| panic!("session 2 is authenticated"));
+        assert_eq!(session.user(), "alice");
+
+        let session = sessions.remove(&1).unwrap();
+        let Err(session) = session.try_into_authenticated() else {
+            panic!("session 1 is not authenticated");
+        };
+        assert!(matches!(session, AnySession::Connected(_)));
+        assert_eq!(session.try_into_connected().ok().unwrap().peer, "10.0.0.1");
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    task::{Context, Poll, Waker},
+};
+
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Disconnected, Connected, Authenticated),
+    slots = (Disconnected)
+)]
+struct Connection {
+    address: String,
+    token: Option<String>,
+}
+
+async fn resolve(address: &str) -> Option<String> {
+    (!address.is_empty()).then(|| format!("{}:443", address))
+}
+
+#[impl_state]
+impl Connection {
+    #[require(Disconnected)]
+    fn new(address: &str) -> Connection {
+        Connection {
+            address: address.to_string(),
+            token: None,
+        }
+    }
+
+    // the state is injected into the output of the future: `Result<Connection<Connected>, String>`
+    #[require(Disconnected)]
+    #[switch_to(Connected)]
+    async fn connect(self) -> Result<Connection, String> {
+        let Some(address) = resolve(&self.address).await else {
+            return Err(format!("cannot resolve `{}`", self.address));
+        };
+
+        Ok(Connection {
+            address,
+            token: self.token,
+        })
+    }
+
+    // `impl Future<Output = Connection<Authenticated>>`, with an `async move` block
+    #[require(Connected)]
+    #[switch_to(Authenticated)]
+    fn authenticate(self, token: &str) -> impl Future<Output = Connection> {
+        let token = token.to_string();
+        async move {
+            if token.is_empty() {
+                return Connection {
+                    address: self.address,
+                    token: None,
+                };
+            }
+            Connection {
+                address: self.address,
+                token: Some(token),
+            }
+        }
+    }
+
+    // a boxed future, e.g. for the trait objects or the recursive calls
+    #[require(A)]
+    #[switch_to(Disconnected)]
+    fn disconnect(self) -> Pin<Box<dyn Future<Output = Connection> + Send>> {
+        Box::pin(async move {
+            Connection {
+                address: self.address,
+                token: None,
+            }
+        })
+    }
+
+    // the struct literals are rewritten in every branch
+    #[require(Authenticated)]
+    #[switch_to(Connected)]
+    async fn refresh(self, token: Option<&str>) -> Option<Connection> {
+        match token {
+            Some(token) if !token.is_empty() => Some(Connection {
+                address: self.address,
+                token: Some(token.to_string()),
+            }),
+            _ => None,
+        }
+    }
+
+    #[require(Authenticated)]
+    async fn token(&self) -> &str {
+        self.token.as_deref().unwrap_or_default()
+    }
+}
+
+/// A minimal executor for the tests, the futures above never wait for anything
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn async_transitions() {
+        block_on(async {
+            let connection: Connection<Connected> =
+                Connection::new("example.com").connect().await.unwrap();
+            assert_eq!(connection.address, "example.com:443");
+
+            let connection: Connection<Authenticated> = connection.authenticate("secret").await;
+            assert_eq!(connection.token().await, "secret");
+
+            let connection: Connection<Connected> =
+                connection.refresh(Some("new secret")).await.unwrap();
+            assert_eq!(connection.token.as_deref(), Some("new secret"));
+
+            let connection: Connection<Disconnected> = connection.disconnect().await;
+            assert_eq!(connection.token, None);
+        });
+    }
+
+    #[test]
+    fn async_transitions_can_fail() {
+        let error = block_on(Connection::new("").connect()).err();
+        assert_eq!(error, Some("cannot resolve ``".to_string()));
+
+        let connection = block_on(Connection::new("example.com").connect()).unwrap();
+        let connection = block_on(connection.authenticate("secret"));
+        assert!(block_on(connection.refresh(None)).is_none());
+    }
+
+    #[test]
+    fn boxed_futures_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let future = Connection::new("example.com").disconnect();
+        assert_send(&future);
+        assert_eq!(block_on(future).address, "example.com");
+    }
+}
@@ -0,0 +1,77 @@
+use state_shift::TypeStateBuilder;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+// generates `PlayerBuilder`, and `Player::builder()`
+#[derive(TypeStateBuilder, Debug, PartialEq)]
+struct Player {
+    race: Race,
+    level: u8,
+    #[builder(default)]
+    skill_slots: u8,
+    #[builder(optional)]
+    nickname: Option<String>,
+}
+
+// generics and lifetimes are preserved
+#[derive(TypeStateBuilder)]
+struct Guild<'a, T: Clone> {
+    name: &'a str,
+    members: Vec<T>,
+}
+
+/// the builder can be passed around in a specific state
+fn with_race(builder: PlayerBuilder<PlayerRaceUnset, PlayerLevelSet>) -> Player {
+    builder.race(Race::Human).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_works() {
+        let player = Player::builder()
+            .level(10)
+            .nickname("grom".to_string())
+            .race(Race::Orc)
+            .build();
+
+        assert_eq!(
+            player,
+            Player {
+                race: Race::Orc,
+                level: 10,
+                skill_slots: 0,
+                nickname: Some("grom".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn default_and_optional_fields_can_be_set_in_any_state() {
+        let player = Player::builder().skill_slots(3).level(1);
+        let player = with_race(player);
+
+        assert_eq!(player.skill_slots, 3);
+        assert_eq!(player.nickname, None);
+    }
+
+    #[test]
+    fn generic_builder_works() {
+        let guild = Guild::builder()
+            .members(vec![Race::Orc, Race::Human])
+            .name("horde")
+            .build();
+
+        assert_eq!(guild.name, "horde");
+        assert_eq!(guild.members.len(), 2);
+    }
+
+    // this should not compile, `build()` requires all of the required fields
+    // let player = Player::builder().race(Race::Orc).build();
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (Initial),
+    any = true,
+    cell = true // generates `PlayerBuilderCell` and `PlayerBuilderGuard`
+)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+
+    #[require(LevelSet)]
+    fn level_up(&mut self) -> Option<u8> {
+        self.level = self.level.map(|level| level + 1);
+        self.level
+    }
+}
+
+// the players live in the lobby, and they are only reachable through `&mut`
+struct Lobby {
+    players: HashMap<u32, PlayerBuilderCell>,
+}
+
+impl Lobby {
+    fn join(&mut self, id: u32) {
+        self.players
+            .insert(id, PlayerBuilderCell::new(PlayerBuilder::new()));
+    }
+
+    /// Returns `false` if the player has already picked a race
+    fn pick_race(&mut self, id: u32, race: Race) -> bool {
+        let cell = self.players.get_mut(&id).unwrap();
+        match cell.in_state::<Initial>() {
+            // the guard is typed, only the transitions of `Initial` can be called
+            Some(player) => {
+                let player = player.switch(|player| player.set_race(race));
+                assert_eq!(player.race, Some(Race::Orc));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[type_state(states = (Empty, Full), slots = (Empty), any = true, cell = true)]
+struct Slot<T> {
+    item: Option<T>,
+}
+
+#[impl_state]
+impl<T> Slot<T> {
+    #[require(Empty)]
+    fn new() -> Slot<T> {
+        Slot { item: None }
+    }
+
+    #[require(Empty)]
+    #[switch_to(Full)]
+    fn fill(self, item: T) -> Slot<T> {
+        Slot { item: Some(item) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_switch_states_behind_mut() {
+        let mut lobby = Lobby {
+            players: HashMap::new(),
+        };
+        lobby.join(1);
+
+        assert!(lobby.pick_race(1, Race::Orc));
+        assert!(!lobby.pick_race(1, Race::Human));
+
+        let cell = lobby.players.get_mut(&1).unwrap();
+        assert_eq!(cell.state(), PlayerBuilderState::RaceSet);
+    }
+
+    #[test]
+    fn guards_can_be_chained_and_mutated() {
+        let mut cell = PlayerBuilderCell::new(PlayerBuilder::new());
+        {
+            let mut player = cell
+                .in_state::<Initial>()
+                .unwrap()
+                .switch(|player| player.set_race(Race::Human))
+                .switch(|player| player.set_level(1));
+            // `&mut self` methods through `DerefMut`
+            assert_eq!(player.level_up(), Some(2));
+        }
+
+        assert_eq!(cell.state(), PlayerBuilderState::LevelSet);
+        assert!(cell.in_state::<Initial>().is_none());
+
+        let player = cell.into_inner().try_into_level_set().ok().unwrap();
+        assert_eq!(player.level, Some(2));
+    }
+
+    #[test]
+    fn generic_structs_have_cells() {
+        let mut cell = SlotCell::new(Slot::<&str>::new());
+        if let Some(slot) = cell.in_state::<Empty>() {
+            slot.switch(|slot| slot.fill("sword"));
+        }
+
+        assert_eq!(cell.state(), SlotState::Full);
+        assert_eq!(cell.in_state::<Full>().unwrap().item, Some("sword"));
+    }
+}
@@ -0,0 +1,103 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(states = (Initial, RaceSet, LevelSet), slots = (Initial), debug = true)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+}
+
+// `state_names()` and `into_parts()` are only generated with `state_names = true` and `parts = true`,
+// so the struct can have its own
+impl PlayerBuilder<RaceSet> {
+    fn state_names(&self) -> &'static str {
+        "the race is set"
+    }
+
+    fn into_parts(self) -> (Option<Race>, Option<u8>) {
+        (self.race, self.level)
+    }
+}
+
+// one entry per slot, and the generics are required to implement `Debug`
+#[type_state(flags = (name, items), debug = true)]
+struct Inventory<T> {
+    name: Option<String>,
+    items: Vec<T>,
+}
+
+#[impl_state]
+impl<T> Inventory<T> {
+    #[require(name = NameUnset, items = ItemsUnset)]
+    fn new() -> Inventory<T> {
+        Inventory {
+            name: None,
+            items: Vec::new(),
+        }
+    }
+
+    #[require(items = ItemsUnset)]
+    #[switch_to(items)]
+    fn set_items(self, items: Vec<T>) -> Inventory<T> {
+        Inventory {
+            name: self.name,
+            items,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_shows_the_state() {
+        let player = PlayerBuilder::new();
+        assert_eq!(
+            format!("{:?}", player),
+            r#"PlayerBuilder { race: None, level: None, state: "Initial" }"#
+        );
+
+        let player = player.set_race(Race::Human);
+        assert_eq!(
+            format!("{:?}", player),
+            r#"PlayerBuilder { race: Some(Human), level: None, state: "RaceSet" }"#
+        );
+        assert_eq!(player.state_names(), "the race is set");
+        assert!(matches!(player.into_parts(), (Some(Race::Human), None)));
+    }
+
+    #[test]
+    fn debug_shows_the_state_of_each_slot() {
+        let inventory = Inventory::new().set_items(vec![Race::Orc]);
+        assert_eq!(
+            format!("{:?}", inventory),
+            r#"Inventory { name: None, items: [Orc], state: ["NameUnset", "ItemsSet"] }"#
+        );
+    }
+}
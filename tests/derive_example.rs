@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+enum Race {
+    #[default]
+    Orc,
+    Human,
+}
+
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (race: Initial, level: Initial)
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(race = Initial, level = Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(race = Initial)]
+    #[switch_to(race = RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    #[require(level = Initial)]
+    #[switch_to(level = LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+
+    /// the derives are also available in the generic states
+    #[require(race = A, level = B)]
+    fn duplicate(&self) -> PlayerBuilder {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_are_not_blocked_by_the_states() {
+        let player = PlayerBuilder::new().set_race(Race::Human);
+        let copy = player.duplicate();
+        assert_eq!(player, copy);
+
+        let mut players = HashSet::new();
+        players.insert(player);
+        players.insert(copy);
+        assert_eq!(players.len(), 1);
+
+        let player: PlayerBuilder = Default::default();
+        assert_eq!(player, PlayerBuilder::new());
+        assert!(format!("{:?}", player).starts_with("PlayerBuilder { race: None, level: None"));
+    }
+}
@@ -0,0 +1,89 @@
+use state_shift::{impl_state, type_state};
+
+#[type_state(states = (Closed, Open), slots = (Closed))]
+struct Connection {
+    address: String,
+    attempts: u8,
+}
+
+/// The error of `send`, it gives the connection back
+struct SendError<C> {
+    connection: C,
+    reason: String,
+}
+
+#[impl_state]
+impl Connection {
+    #[require(Closed)]
+    fn new(address: &str) -> Connection {
+        Connection {
+            address: address.to_string(),
+            attempts: 0,
+        }
+    }
+
+    // `Result<Connection<Open>, (Connection<Closed>, String)>`:
+    // the connection is given back in its old state on failure, so it can be retried
+    #[require(Closed)]
+    #[switch_to(Open, on_err = keep)]
+    fn open(mut self) -> Result<Connection, (Connection, String)> {
+        self.attempts += 1;
+        if self.address.is_empty() {
+            return Err((self, "no address".to_string()));
+        }
+        Ok(Connection {
+            address: self.address,
+            attempts: self.attempts,
+        })
+    }
+
+    // the error type can be any type with the struct in it: `Result<Connection<Closed>, SendError<Connection<Open>>>`
+    #[require(Open)]
+    #[switch_to(Closed, on_err = keep)]
+    fn send(self, message: &str) -> Result<Connection, SendError<Connection>> {
+        if message.is_empty() {
+            return Err(SendError {
+                connection: self,
+                reason: "empty message".to_string(),
+            });
+        }
+        Ok(Connection {
+            address: self.address,
+            attempts: self.attempts,
+        })
+    }
+
+    #[require(A)]
+    fn attempts(&self) -> u8 {
+        self.attempts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_transitions_give_the_object_back() {
+        let (connection, error) = Connection::new("").open().err().unwrap();
+        assert_eq!(error, "no address");
+
+        // still `Connection<Closed>`, so it can be opened again
+        let connection: Connection<Closed> = connection;
+        let (connection, _) = connection.open().err().unwrap();
+        assert_eq!(connection.attempts(), 2);
+    }
+
+    #[test]
+    fn successful_transitions_switch_states() {
+        let connection: Connection<Open> = Connection::new("example.com").open().ok().unwrap();
+
+        let error = connection.send("").err().unwrap();
+        assert_eq!(error.reason, "empty message");
+        let connection: Connection<Open> = error.connection;
+
+        let connection: Connection<Closed> = connection.send("hello").ok().unwrap();
+        assert_eq!(connection.address, "example.com");
+        assert_eq!(connection.attempts(), 1);
+    }
+}
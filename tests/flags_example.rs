@@ -0,0 +1,148 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug)]
+struct Player {
+    race: Race,
+    level: u8,
+    skill_slots: u8,
+    spell_slots: u8,
+}
+
+#[derive(Debug, PartialEq)]
+enum Race {
+    #[allow(unused)]
+    Orc,
+    Human,
+}
+
+// generates `RaceSet`/`RaceUnset`, `LevelSet`/`LevelUnset`, ... and a slot for each flag, starting at `...Unset`
+#[type_state(flags = (race, level, skill_slots, spell_slots), state_names = true)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+    skill_slots: Option<u8>,
+    spell_slots: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(
+        race = RaceUnset,
+        level = LevelUnset,
+        skill_slots = SkillSlotsUnset,
+        spell_slots = SpellSlotsUnset
+    )]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+            skill_slots: None,
+            spell_slots: None,
+        }
+    }
+
+    #[require(race = RaceUnset)] // flags can still be used as named slots
+    #[switch_to(race)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+            skill_slots: self.skill_slots,
+            spell_slots: self.spell_slots,
+        }
+    }
+
+    #[require(race)] // `race` is set, the others can be anything
+    #[switch_to(level)] // `level` becomes set, the others are unchanged
+    fn set_level(self, level_modifier: u8) -> PlayerBuilder {
+        let level = match self.race {
+            Some(Race::Orc) => level_modifier + 2, // Orc's have +2 level advantage
+            Some(Race::Human) => level_modifier,   // humans are weak
+            None => unreachable!("type safety ensures that `race` is initialized"),
+        };
+
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+            skill_slots: self.skill_slots,
+            spell_slots: self.spell_slots,
+        }
+    }
+
+    #[require(race)]
+    #[switch_to(skill_slots)]
+    fn set_skill_slots(self, skill_slot_modifier: u8) -> PlayerBuilder {
+        let skill_slots = match self.race {
+            Some(Race::Orc) => skill_slot_modifier,
+            Some(Race::Human) => skill_slot_modifier + 1, // Human's have +1 skill slot advantage
+            None => unreachable!("type safety ensures that `race` should be initialized"),
+        };
+
+        PlayerBuilder {
+            race: self.race,
+            level: self.level,
+            skill_slots: Some(skill_slots),
+            spell_slots: self.spell_slots,
+        }
+    }
+
+    #[require(level, skill_slots)]
+    #[switch_to(spell_slots)]
+    fn set_spells(self, spell_slot_modifier: u8) -> PlayerBuilder {
+        let level = self
+            .level
+            .expect("type safety ensures that `level` is initialized");
+        let skill_slots = self
+            .skill_slots
+            .expect("type safety ensures that `skill_slots` is initialized");
+
+        let spell_slots = level / 10 + skill_slots + spell_slot_modifier;
+
+        PlayerBuilder {
+            race: self.race,
+            level: self.level,
+            skill_slots: self.skill_slots,
+            spell_slots: Some(spell_slots),
+        }
+    }
+
+    #[require(race, level, skill_slots, spell_slots)]
+    fn build(self) -> Player {
+        Player {
+            race: self.race.expect("type safety ensures this is set"),
+            level: self.level.expect("type safety ensures this is set"),
+            skill_slots: self.skill_slots.expect("type safety ensures this is set"),
+            spell_slots: self.spell_slots.expect("type safety ensures this is set"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_player_creation_works() {
+        let player = PlayerBuilder::new()
+            .set_race(Race::Human)
+            .set_skill_slots(10)
+            .set_level(10)
+            .set_spells(10)
+            .build();
+
+        assert_eq!(player.race, Race::Human);
+        assert_eq!(player.level, 10);
+        assert_eq!(player.skill_slots, 11);
+        assert_eq!(player.spell_slots, 22);
+    }
+
+    #[test]
+    fn state_names_of_each_slot_are_reflected() {
+        let player = PlayerBuilder::new().set_race(Race::Orc).set_skill_slots(1);
+
+        assert_eq!(
+            player.state_names(),
+            ["RaceSet", "LevelUnset", "SkillSlotsSet", "SpellSlotsUnset"]
+        );
+    }
+}
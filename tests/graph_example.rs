@@ -0,0 +1,156 @@
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Closed, Open, Locked),
+    slots = (door: Closed, alarm: Closed in (Closed, Open))
+)]
+struct House {
+    visits: u32,
+}
+
+// the graph covers the methods of the `#[impl_state]` block with `dot = true`
+#[impl_state(dot = true, mermaid = true)]
+impl House {
+    #[require(door = Closed, alarm = Closed)]
+    fn new() -> House {
+        House { visits: 0 }
+    }
+
+    #[require(door = Closed)]
+    #[switch_to(door = Open)]
+    fn open(self) -> House {
+        House {
+            visits: self.visits + 1,
+        }
+    }
+
+    #[require(door = Closed)]
+    #[switch_to(door = Locked)]
+    fn lock(self) -> House {
+        House {
+            visits: self.visits,
+        }
+    }
+
+    #[require(door = A)]
+    #[switch_to(door = Closed)]
+    fn close(self) -> House {
+        House {
+            visits: self.visits,
+        }
+    }
+
+    #[require(alarm = Closed)]
+    #[switch_to(alarm = Open)]
+    fn trigger_alarm(self) -> House {
+        House {
+            visits: self.visits,
+        }
+    }
+}
+
+#[type_state(states = (Draft, Review, Published), slots = (Draft))]
+struct Post {
+    text: String,
+}
+
+// the diagram is also shown in the docs of the generated `impl Post<Draft>` block
+#[impl_state(mermaid = true)]
+impl Post {
+    #[require(Draft)]
+    fn new(text: &str) -> Post {
+        Post {
+            text: text.to_string(),
+        }
+    }
+
+    #[require(Draft)]
+    #[switch_to(Review)]
+    fn submit(self) -> Post {
+        Post { text: self.text }
+    }
+
+    #[require(Review)]
+    #[switch_to(Published)]
+    fn approve(self) -> Post {
+        Post { text: self.text }
+    }
+
+    #[require(Review)]
+    #[switch_to(Draft)]
+    fn reject(self) -> Post {
+        Post { text: self.text }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_graph_is_exported_as_dot() {
+        let expected = r#"digraph House {
+    label = "House";
+    subgraph cluster_0 {
+        label = "door";
+        "0:start" [shape = point];
+        "0:Closed" [label = "Closed"];
+        "0:Open" [label = "Open"];
+        "0:Locked" [label = "Locked"];
+        "0:start" -> "0:Closed";
+        "0:Closed" -> "0:Open" [label = "open"];
+        "0:Closed" -> "0:Locked" [label = "lock"];
+        "0:Open" -> "0:Closed" [label = "close"];
+        "0:Locked" -> "0:Closed" [label = "close"];
+    }
+    subgraph cluster_1 {
+        label = "alarm";
+        "1:start" [shape = point];
+        "1:Closed" [label = "Closed"];
+        "1:Open" [label = "Open"];
+        "1:start" -> "1:Closed";
+        "1:Closed" -> "1:Open" [label = "trigger_alarm"];
+    }
+}
+"#;
+        assert_eq!(House::STATE_GRAPH_DOT, expected);
+
+        let house = House::new().open().close().trigger_alarm();
+        assert_eq!(house.visits, 1);
+    }
+
+    #[test]
+    fn state_graph_is_exported_as_mermaid() {
+        let expected = "stateDiagram-v2
+    Draft
+    Review
+    Published
+    [*] --> Draft
+    Draft --> Review: submit
+    Review --> Published: approve
+    Review --> Draft: reject
+";
+        assert_eq!(Post::STATE_GRAPH_MERMAID, expected);
+        assert_eq!(Post::new("hello").submit().approve().text, "hello");
+
+        let expected = r#"stateDiagram-v2
+    state "door" as slot0 {
+        state "Closed" as slot0_Closed
+        state "Open" as slot0_Open
+        state "Locked" as slot0_Locked
+        [*] --> slot0_Closed
+        slot0_Closed --> slot0_Open: open
+        slot0_Closed --> slot0_Locked: lock
+        slot0_Open --> slot0_Closed: close
+        slot0_Locked --> slot0_Closed: close
+    }
+    state "alarm" as slot1 {
+        state "Closed" as slot1_Closed
+        state "Open" as slot1_Open
+        [*] --> slot1_Closed
+        slot1_Closed --> slot1_Open: trigger_alarm
+    }
+"#;
+        assert_eq!(House::STATE_GRAPH_MERMAID, expected);
+    }
+}
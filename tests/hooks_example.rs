@@ -0,0 +1,256 @@
+use std::cell::Cell;
+
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (Initial),
+    hooks = true // generates `OnEnterPlayerBuilder` and `OnExitPlayerBuilder`
+)]
+struct PlayerBuilder {
+    race: Option<String>,
+    level: Option<u8>,
+    history: Vec<String>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+            history: Vec::new(),
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: &str) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race.to_string()),
+            level: self.level,
+            history: self.history,
+        }
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+            history: self.history,
+        }
+    }
+
+    // `Option` and `Result`: `OnEnter` is only called for the object in `Some` or `Ok`
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn try_set_level(self, level: u8) -> Option<PlayerBuilder> {
+        if level == 0 {
+            return None;
+        }
+        Some(PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+            history: self.history,
+        })
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn parse_level(self, level: &str) -> Result<PlayerBuilder, String> {
+        let level = level.parse::<u8>().map_err(|error| error.to_string())?;
+        Ok(PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+            history: self.history,
+        })
+    }
+
+    #[require(A)]
+    #[switch_to(Initial)]
+    fn reset(self) -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+            history: self.history,
+        }
+    }
+}
+
+thread_local! {
+    static EXITS: Cell<u32> = const { Cell::new(0) };
+}
+
+/// records the transitions into the object
+fn record<From: SealerPlayerBuilder, To: SealerPlayerBuilder>(object: &mut PlayerBuilder<To>) {
+    object
+        .history
+        .push(format!("{} -> {}", From::NAME, To::NAME));
+}
+
+impl OnEnterPlayerBuilder<PlayerBuilder<Initial>> for Initial {
+    fn on_enter<From: SealerPlayerBuilder>(object: &mut PlayerBuilder<Initial>) {
+        record::<From, Initial>(object);
+    }
+}
+
+impl OnEnterPlayerBuilder<PlayerBuilder<RaceSet>> for RaceSet {
+    fn on_enter<From: SealerPlayerBuilder>(object: &mut PlayerBuilder<RaceSet>) {
+        record::<From, RaceSet>(object);
+    }
+}
+
+impl OnEnterPlayerBuilder<PlayerBuilder<LevelSet>> for LevelSet {
+    fn on_enter<From: SealerPlayerBuilder>(object: &mut PlayerBuilder<LevelSet>) {
+        assert!(object.level.is_some(), "the level is set in `LevelSet`");
+        record::<From, LevelSet>(object);
+    }
+}
+
+// the states that don't need a hook use the default (empty) one
+impl<Object> OnExitPlayerBuilder<Object> for Initial {}
+impl<Object> OnExitPlayerBuilder<Object> for RaceSet {}
+
+impl OnExitPlayerBuilder<PlayerBuilder<LevelSet>> for LevelSet {
+    fn on_exit<To: SealerPlayerBuilder>(_object: &PlayerBuilder<LevelSet>) {
+        EXITS.with(|exits| exits.set(exits.get() + 1));
+    }
+}
+
+// only the listed states implement the hooks, the others get the empty ones
+mod listed_hooks {
+    use std::cell::Cell;
+
+    use state_shift::{impl_state, type_state};
+
+    #[type_state(states = (Closed, Open), slots = (Closed), hooks = (Open))]
+    pub struct Connection {
+        pub address: String,
+    }
+
+    #[impl_state]
+    impl Connection {
+        #[require(Closed)]
+        pub fn new(address: &str) -> Connection {
+            Connection {
+                address: address.to_string(),
+            }
+        }
+
+        // the hooks are only called for the object in `Ok`, the error has it in its old state
+        #[require(Closed)]
+        #[switch_to(Open, on_err = keep)]
+        pub fn open(self) -> Result<Connection, (Connection, String)> {
+            if self.address.is_empty() {
+                return Err((self, "no address".to_string()));
+            }
+            Ok(Connection {
+                address: self.address,
+            })
+        }
+
+        #[require(Open)]
+        #[switch_to(Closed)]
+        pub fn close(self) -> Connection {
+            Connection {
+                address: self.address,
+            }
+        }
+    }
+
+    thread_local! {
+        pub static OPENED: Cell<u32> = const { Cell::new(0) };
+        pub static CLOSED: Cell<u32> = const { Cell::new(0) };
+    }
+
+    impl OnEnterConnection<Connection<Open>> for Open {
+        fn on_enter<From: SealerConnection>(_object: &mut Connection<Open>) {
+            OPENED.with(|opened| opened.set(opened.get() + 1));
+        }
+    }
+
+    impl OnExitConnection<Connection<Open>> for Open {
+        fn on_exit<To: SealerConnection>(_object: &Connection<Open>) {
+            CLOSED.with(|closed| closed.set(closed.get() + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_are_called_on_transitions() {
+        let player = PlayerBuilder::new().set_race("orc").set_level(10);
+        assert_eq!(
+            player.history,
+            ["Initial -> RaceSet", "RaceSet -> LevelSet"]
+        );
+
+        let player = player.reset();
+        assert_eq!(EXITS.with(Cell::get), 1);
+        assert_eq!(
+            player.history,
+            [
+                "Initial -> RaceSet",
+                "RaceSet -> LevelSet",
+                "LevelSet -> Initial"
+            ]
+        );
+    }
+
+    #[test]
+    fn hooks_are_called_on_fallible_transitions() {
+        assert!(PlayerBuilder::new()
+            .set_race("orc")
+            .try_set_level(0)
+            .is_none());
+        let player = PlayerBuilder::new()
+            .set_race("orc")
+            .try_set_level(5)
+            .unwrap();
+        assert_eq!(
+            player.history,
+            ["Initial -> RaceSet", "RaceSet -> LevelSet"]
+        );
+
+        assert!(PlayerBuilder::new()
+            .set_race("orc")
+            .parse_level("x")
+            .is_err());
+        let player = PlayerBuilder::new()
+            .set_race("orc")
+            .parse_level("7")
+            .unwrap();
+        assert_eq!(player.level, Some(7));
+        assert_eq!(
+            player.history,
+            ["Initial -> RaceSet", "RaceSet -> LevelSet"]
+        );
+    }
+
+    #[test]
+    fn listed_hooks_are_called_on_ok_only() {
+        use listed_hooks::{Closed, Connection, CLOSED, OPENED};
+
+        let Err((connection, error)) = Connection::new("").open() else {
+            panic!("the connection has no address");
+        };
+        let _: &Connection<Closed> = &connection;
+        assert_eq!(error, "no address");
+        assert_eq!(OPENED.with(Cell::get), 0);
+
+        let Ok(connection) = Connection::new("localhost").open() else {
+            panic!("the connection has an address");
+        };
+        assert_eq!(OPENED.with(Cell::get), 1);
+
+        let connection = connection.close();
+        assert_eq!(CLOSED.with(Cell::get), 1);
+        assert_eq!(connection.address, "localhost");
+    }
+}
@@ -0,0 +1,125 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(states = (Initial, RaceSet, LevelSet), slots = (Initial))]
+#[invariant(RaceSet => self.race.is_some())]
+#[invariant(LevelSet => self.race.is_some() && self::is_valid_level(&self.level))]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+/// `self::` in an invariant is a path, not the object
+fn is_valid_level(level: &Option<u8>) -> bool {
+    level.is_some_and(|level| level > 0)
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+
+    /// a buggy transition, it forgets to set the level
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn skip_level(self) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: self.level,
+        }
+    }
+
+    // `Option` and `Result`: the invariants are checked on the object in `Some` or `Ok`
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn try_set_level(self, level: u8) -> Option<PlayerBuilder> {
+        Some(PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        })
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn parse_level(self, level: &str) -> Result<PlayerBuilder, String> {
+        let level = level.parse::<u8>().map_err(|error| error.to_string())?;
+        Ok(PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        })
+    }
+
+    #[require(LevelSet)]
+    fn level(&self) -> u8 {
+        self.level
+            .expect("the invariant of `LevelSet` ensures this is set")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invariants_hold() {
+        let player = PlayerBuilder::new().set_race(Race::Orc).set_level(10);
+        assert_eq!(player.level(), 10);
+
+        let player = PlayerBuilder::new().set_race(Race::Human).try_set_level(20);
+        assert_eq!(player.unwrap().level(), 20);
+
+        // the error has no object to check
+        let result = PlayerBuilder::new().set_race(Race::Human).parse_level("x");
+        assert!(result.is_err());
+    }
+
+    // the invariants are only checked in debug builds
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "the invariant of `LevelSet` is violated")]
+    fn broken_invariant_is_caught() {
+        let _ = PlayerBuilder::new().set_race(Race::Human).skip_level();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Function `try_set_level`: the invariant of `LevelSet` is violated")]
+    fn broken_invariant_is_caught_in_option() {
+        let _ = PlayerBuilder::new().set_race(Race::Orc).try_set_level(0);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Function `parse_level`: the invariant of `LevelSet` is violated")]
+    fn broken_invariant_is_caught_in_result() {
+        let _ = PlayerBuilder::new().set_race(Race::Orc).parse_level("0");
+    }
+}
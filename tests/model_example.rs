@@ -0,0 +1,112 @@
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Empty, Loaded),
+    slots = (magazine: Empty),
+    flags = (safety),
+    transitions = (Empty -> Loaded, _ -> Empty)
+)]
+pub(crate) struct Rifle {
+    rounds: u8,
+}
+
+#[impl_state(json = true)]
+impl Rifle {
+    #[require(magazine = Empty, safety = SafetyUnset)]
+    pub fn new() -> Rifle {
+        Rifle { rounds: 0 }
+    }
+
+    #[require(magazine = Empty)]
+    #[switch_to(magazine = Loaded)]
+    pub(crate) fn load(self, rounds: u8) -> Rifle {
+        Rifle { rounds }
+    }
+
+    #[require(magazine = A)]
+    #[switch_to(magazine = Empty)]
+    fn unload(self) -> Rifle {
+        Rifle { rounds: 0 }
+    }
+
+    #[require(magazine = Loaded, safety)]
+    fn rounds(&self) -> u8 {
+        self.rounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::{json, Value};
+
+    #[test]
+    fn model_is_exported_as_json() {
+        let model: Value = serde_json::from_str(Rifle::STATE_MODEL_JSON).unwrap();
+
+        assert_eq!(model["version"], 1);
+        assert_eq!(model["name"], "Rifle");
+        assert_eq!(model["visibility"], "pub(crate)");
+        assert_eq!(
+            model["states"],
+            json!(["Empty", "Loaded", "SafetyUnset", "SafetySet"])
+        );
+        assert_eq!(
+            model["slots"],
+            json!([
+                { "name": "magazine", "default": "Empty", "states": null, "flag": false },
+                {
+                    "name": "safety",
+                    "default": "SafetyUnset",
+                    "states": ["SafetyUnset", "SafetySet"],
+                    "flag": true
+                }
+            ])
+        );
+        assert_eq!(
+            model["transitions"],
+            json!([{ "from": "Empty", "to": "Loaded" }, { "from": null, "to": "Empty" }])
+        );
+        assert_eq!(model["terminal"], json!([]));
+        assert_eq!(
+            model["methods"],
+            json!([
+                {
+                    "name": "new",
+                    "visibility": "pub",
+                    "required": ["Empty", "SafetyUnset"],
+                    "switched_to": null,
+                    "generics": []
+                },
+                {
+                    "name": "load",
+                    "visibility": "pub(crate)",
+                    "required": ["Empty", "RifleStateSafety"],
+                    "switched_to": ["Loaded", "RifleStateSafety"],
+                    "generics": ["RifleStateSafety"]
+                },
+                {
+                    "name": "unload",
+                    "visibility": "",
+                    "required": ["A", "RifleStateSafety"],
+                    "switched_to": ["Empty", "RifleStateSafety"],
+                    "generics": ["A", "RifleStateSafety"]
+                },
+                {
+                    "name": "rounds",
+                    "visibility": "",
+                    "required": ["Loaded", "SafetySet"],
+                    "switched_to": null,
+                    "generics": []
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn methods_follow_the_model() {
+        let rifle = Rifle::new().load(5);
+        assert_eq!(rifle.unload().load(3).rounds, 3);
+    }
+}
@@ -0,0 +1,65 @@
+// `#[impl_state]` finds the `#[type_state]` declaration of the struct by its path,
+// so the structs with the same name in different modules don't mix up,
+// and the order of the items doesn't matter
+
+mod lamps {
+    use state_shift::{impl_state, type_state};
+
+    // this block comes before the declaration of the struct
+    #[impl_state]
+    impl Lamp {
+        #[require(power = Off)]
+        pub fn new(watts: u32) -> Lamp {
+            Lamp { watts }
+        }
+
+        #[require(power = Off)]
+        #[switch_to(power = On)]
+        pub fn turn_on(self) -> Lamp {
+            Lamp { watts: self.watts }
+        }
+    }
+
+    #[type_state(states = (Off, On), slots = (power: Off))]
+    pub struct Lamp {
+        pub watts: u32,
+    }
+}
+
+// a struct with the same name, with positional slots
+mod tanks {
+    use state_shift::{impl_state, type_state};
+
+    #[type_state(states = (Empty, Full), slots = (Empty))]
+    pub struct Lamp {
+        pub oil: u32,
+    }
+
+    #[impl_state]
+    impl Lamp {
+        #[require(Empty)]
+        pub fn new() -> Lamp {
+            Lamp { oil: 0 }
+        }
+
+        #[require(Empty)]
+        #[switch_to(Full)]
+        pub fn fill(self) -> Lamp {
+            Lamp { oil: self.oil + 1 }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impl_blocks_find_their_struct() {
+        let lamp: lamps::Lamp<lamps::On> = lamps::Lamp::new(60).turn_on();
+        assert_eq!(lamp.watts, 60);
+
+        let lamp: tanks::Lamp<tanks::Full> = tanks::Lamp::new().fill();
+        assert_eq!(lamp.oil, 1);
+    }
+}
@@ -0,0 +1,87 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+// `#[type_state]` adds `#[must_use = "state transition result must be used"]` to the struct,
+// unless it already has one
+#[type_state(states = (Initial, RaceSet, LevelSet), slots = (Initial))]
+#[must_use = "a player builder does nothing until it is built"]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    // `PlayerBuilder` is already `#[must_use]`, so nothing is added here
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    // `Option` is not `#[must_use]`, so `#[impl_state]` adds it to the method
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn try_set_level(self, level: u8) -> Option<PlayerBuilder> {
+        (level <= 100).then_some(())?;
+        Some(PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        })
+    }
+
+    // the user's own `#[must_use]` is kept
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    #[must_use = "the level might be rejected"]
+    fn checked_set_level(self, level: u8) -> Result<PlayerBuilder, String> {
+        if level > 100 {
+            return Err(format!("{} is too high", level));
+        }
+        Ok(PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        })
+    }
+
+    // the state doesn't change, so there is nothing to add
+    #[require(A)]
+    fn race(&self) -> Option<&Race> {
+        self.race.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transition_results_are_used() {
+        let player = PlayerBuilder::new().set_race(Race::Orc);
+        assert_eq!(player.race(), Some(&Race::Orc));
+
+        let player = player.try_set_level(10).unwrap();
+        assert_eq!(player.level, Some(10));
+
+        let rejected = PlayerBuilder::new()
+            .set_race(Race::Human)
+            .checked_set_level(200);
+        assert_eq!(rejected.err(), Some("200 is too high".to_string()));
+    }
+}
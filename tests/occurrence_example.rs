@@ -0,0 +1,146 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(states = (Initial, RaceSet, LevelSet), slots = (Initial))]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[type_state(states = (Waiting, Done), slots = (Waiting))]
+struct Party {
+    leader: Option<Race>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    // the occurrences that are written with their states keep them,
+    // so the same struct can be returned in different states
+    #[require(RaceSet)]
+    fn finish_and_invite(self, level: u8) -> (PlayerBuilder<LevelSet>, PlayerBuilder<Initial>) {
+        let player = PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        };
+        (player, PlayerBuilder::new())
+    }
+
+    // the others get the `#[switch_to]` states as usual, the generic states work too:
+    // `(PlayerBuilder<RaceSet>, PlayerBuilder<A>)`
+    #[require(A)]
+    #[switch_to(RaceSet)]
+    fn with_race(self, race: Race) -> (PlayerBuilder, PlayerBuilder<A>) {
+        let copy = PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        };
+        (copy, self)
+    }
+
+    // other typestate structs are written with their states anyway
+    #[require(RaceSet)]
+    fn lead(self) -> (Party<Done>, PlayerBuilder<RaceSet>) {
+        let party = Party::new().close(self.race.clone());
+        (party, self)
+    }
+}
+
+#[impl_state]
+impl Party {
+    #[require(Waiting)]
+    fn new() -> Party {
+        Party { leader: None }
+    }
+
+    #[require(Waiting)]
+    #[switch_to(Done)]
+    fn close(self, leader: Option<Race>) -> Party {
+        Party { leader }
+    }
+}
+
+// with the struct's own generics, the states come after them: `Slot<T, Empty>`
+#[type_state(states = (Empty, Full), slots = (Empty))]
+struct Slot<T> {
+    item: Option<T>,
+}
+
+#[impl_state]
+impl<T> Slot<T> {
+    #[require(Empty)]
+    fn new() -> Slot<T> {
+        Slot { item: None }
+    }
+
+    // `(Slot<T, Full>, Slot<T, Empty>)`
+    #[require(Empty)]
+    #[switch_to(Full)]
+    fn fill_and_spare(self, item: T) -> (Slot<T>, Slot<T, Empty>) {
+        (Slot { item: Some(item) }, Slot::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn occurrences_get_their_own_states() {
+        let (player, invited) = PlayerBuilder::new()
+            .set_race(Race::Orc)
+            .finish_and_invite(5);
+        let player: PlayerBuilder<LevelSet> = player;
+        let invited: PlayerBuilder<Initial> = invited;
+        assert_eq!(player.level, Some(5));
+        assert_eq!(invited.race, None);
+
+        // the invited player goes on with its own transitions
+        let invited = invited.set_race(Race::Human);
+        assert_eq!(invited.race, Some(Race::Human));
+    }
+
+    #[test]
+    fn explicit_states_mix_with_switch_to_and_generics() {
+        let (copy, original) = PlayerBuilder::new().with_race(Race::Human);
+        let copy: PlayerBuilder<RaceSet> = copy;
+        let original: PlayerBuilder<Initial> = original;
+        assert_eq!(copy.race, Some(Race::Human));
+        assert_eq!(original.race, None);
+
+        let (party, leader) = copy.lead();
+        assert_eq!(party.leader, Some(Race::Human));
+        assert_eq!(leader.race, Some(Race::Human));
+    }
+
+    #[test]
+    fn generic_structs_are_written_with_their_states() {
+        let (full, spare) = Slot::new().fill_and_spare("sword");
+        let full: Slot<&str, Full> = full;
+        let spare: Slot<&str, Empty> = spare;
+        assert_eq!(full.item, Some("sword"));
+        let (shield, _) = spare.fill_and_spare("shield");
+        assert_eq!(shield.item, Some("shield"));
+    }
+}
@@ -0,0 +1,110 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (Initial),
+    state_names = true,
+    parts = true
+)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+
+    #[require(LevelSet)]
+    fn summary(&self) -> (Race, u8) {
+        (
+            self.race.clone().expect("type safety ensures this is set"),
+            self.level.expect("type safety ensures this is set"),
+        )
+    }
+}
+
+/// pretend that this is what we stored on the disk
+struct Saved {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+fn save(player: PlayerBuilder<LevelSet>) -> Saved {
+    let PlayerBuilderParts { race, level } = player.into_parts();
+    Saved { race, level }
+}
+
+fn load(saved: Saved) -> Option<PlayerBuilder<LevelSet>> {
+    if saved.race.is_none() || saved.level.is_none() {
+        return None;
+    }
+    let parts = PlayerBuilderParts {
+        race: saved.race,
+        level: saved.level,
+    };
+    // SAFETY: both of the fields are checked above
+    Some(unsafe { PlayerBuilder::from_parts(parts) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_can_be_restored_from_their_parts() {
+        let player = PlayerBuilder::new().set_race(Race::Orc).set_level(10);
+        let saved = save(player);
+
+        let player = load(saved).unwrap();
+        assert_eq!(player.summary(), (Race::Orc, 10));
+
+        assert!(load(Saved {
+            race: None,
+            level: Some(1),
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn state_can_be_assumed() {
+        let mut player = PlayerBuilder::new();
+        player.race = Some(Race::Human);
+        player.level = Some(3);
+
+        // SAFETY: the fields are set by hand, which is what the transitions would do
+        let player = unsafe { player.assume_state::<LevelSet>() };
+        assert_eq!(player.summary(), (Race::Human, 3));
+        assert_eq!(player.state_names(), ["LevelSet"]);
+    }
+}
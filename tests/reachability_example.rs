@@ -0,0 +1,110 @@
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Closed, Open, Locked),
+    slots = (door: Closed, alarm: Closed in (Closed, Open))
+)]
+struct Vault {
+    gold: u32,
+}
+
+// all of the states can be reached, and something can be done in each of them,
+// otherwise this block would not compile
+#[impl_state(reachability = deny)]
+impl Vault {
+    #[require(door = Closed, alarm = Closed)]
+    fn new(gold: u32) -> Vault {
+        Vault { gold }
+    }
+
+    #[require(door = Closed)]
+    #[switch_to(door = Open)]
+    fn open(self) -> Vault {
+        Vault { gold: self.gold }
+    }
+
+    #[require(door = Closed)]
+    #[switch_to(door = Locked)]
+    fn lock(self) -> Vault {
+        Vault { gold: self.gold }
+    }
+
+    #[require(door = A)]
+    #[switch_to(door = Closed)]
+    fn close(self) -> Vault {
+        Vault { gold: self.gold }
+    }
+
+    #[require(door = Open, alarm = Closed)]
+    fn take(&mut self, amount: u32) -> u32 {
+        let taken = amount.min(self.gold);
+        self.gold -= taken;
+        taken
+    }
+
+    #[require(door = Locked, alarm = Closed)]
+    #[switch_to(alarm = Open)]
+    fn trigger_alarm(self) -> Vault {
+        Vault { gold: self.gold }
+    }
+
+    #[require(alarm = Open)]
+    fn alarm_is_ringing(&self) -> bool {
+        true
+    }
+}
+
+// `reachability = warn` reports the same findings as warnings
+#[allow(deprecated)]
+mod draft {
+    use state_shift::{impl_state, type_state};
+
+    #[type_state(states = (Draft, Review, Archived), slots = (Draft))]
+    pub struct Post {
+        pub text: String,
+    }
+
+    // warning: `Post`: `Review` of slot 1 is a dead end: no method can be called in it
+    // warning: `Post`: `Archived` of slot 1 is unreachable from its default state `Draft`, so `restore` can never be called
+    #[impl_state(reachability = warn)]
+    impl Post {
+        #[require(Draft)]
+        pub fn new(text: &str) -> Post {
+            Post {
+                text: text.to_string(),
+            }
+        }
+
+        #[require(Draft)]
+        #[switch_to(Review)]
+        pub fn submit(self) -> Post {
+            Post { text: self.text }
+        }
+
+        #[require(Archived)]
+        pub fn restore(self) -> String {
+            self.text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_state_machine_works() {
+        let mut vault = Vault::new(10).open();
+        assert_eq!(vault.take(3), 3);
+
+        let vault = vault.close().lock().trigger_alarm();
+        assert!(vault.alarm_is_ringing());
+        assert_eq!(vault.gold, 7);
+    }
+
+    #[test]
+    fn warnings_do_not_change_the_code() {
+        let post = draft::Post::new("hello").submit();
+        assert_eq!(post.text, "hello");
+    }
+}
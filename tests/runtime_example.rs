@@ -0,0 +1,139 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (Initial),
+    any = true // required by `#[impl_state(runtime = true)]`
+)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+// generates `PlayerBuilderEvent`, `PlayerBuilderTransitionError` and `AnyPlayerBuilder::transition()`
+#[impl_state(runtime = true)]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+
+    #[require(A)]
+    #[switch_to(Initial)]
+    fn reset(self) -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    /// doesn't take `self` by value, so it is not an event
+    #[require(A)]
+    fn level(&self) -> Option<u8> {
+        self.level
+    }
+}
+
+/// pretend that this comes from the network
+fn parse_event(input: &str) -> PlayerBuilderEvent {
+    match input {
+        "orc" => PlayerBuilderEvent::SetRace(Race::Orc),
+        "human" => PlayerBuilderEvent::SetRace(Race::Human),
+        "reset" => PlayerBuilderEvent::Reset,
+        level => PlayerBuilderEvent::SetLevel(level.parse().unwrap()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legal_transitions_are_dispatched() {
+        let mut player: AnyPlayerBuilder = PlayerBuilder::new().into();
+
+        for input in ["orc", "reset", "human", "10"] {
+            player = player.transition(parse_event(input)).unwrap();
+        }
+
+        assert_eq!(player.state(), PlayerBuilderState::LevelSet);
+        let player = player
+            .try_into_level_set()
+            .unwrap_or_else(|_| panic!("player is in `LevelSet`"));
+        assert_eq!(player.race, Some(Race::Human));
+        assert_eq!(player.level(), Some(10));
+    }
+
+    #[test]
+    fn illegal_transitions_are_rejected() {
+        let player: AnyPlayerBuilder = PlayerBuilder::new().into();
+
+        let Err(error) = player.transition(parse_event("10")) else {
+            panic!("`set_level` is not allowed in `Initial`");
+        };
+        assert_eq!(error.object.state(), PlayerBuilderState::Initial);
+        assert_eq!(error.event.name(), "set_level");
+        assert_eq!(
+            error.to_string(),
+            "`set_level` is not allowed in the `Initial` state of `PlayerBuilder`"
+        );
+
+        // the object is not lost, and can still be used
+        let player = error.object.transition(parse_event("human")).unwrap();
+        assert_eq!(player.state(), PlayerBuilderState::RaceSet);
+    }
+
+    #[test]
+    fn transition_errors_are_std_errors() {
+        fn replay(inputs: &[&str]) -> Result<AnyPlayerBuilder, Box<dyn std::error::Error>> {
+            let mut player: AnyPlayerBuilder = PlayerBuilder::new().into();
+            for input in inputs {
+                player = player.transition(parse_event(input))?;
+            }
+            Ok(player)
+        }
+
+        let Ok(player) = replay(&["orc", "10"]) else {
+            panic!("`orc` and `10` are legal");
+        };
+        assert_eq!(player.state(), PlayerBuilderState::LevelSet);
+
+        let Err(error) = replay(&["orc", "reset", "10"]) else {
+            panic!("`set_level` is not allowed after `reset`");
+        };
+        assert_eq!(
+            error.to_string(),
+            "`set_level` is not allowed in the `Initial` state of `PlayerBuilder`"
+        );
+        assert!(error
+            .downcast_ref::<PlayerBuilderTransitionError>()
+            .is_some());
+    }
+}
@@ -0,0 +1,67 @@
+use state_shift::TypeStateBuilder;
+
+#[derive(Debug, PartialEq)]
+enum Race {
+    Orc,
+    Human,
+}
+
+// the fields have to be set in this order: `race`, then `level`, then `skill_slots`
+#[derive(TypeStateBuilder, Debug, PartialEq)]
+#[builder(sequence = (race, level, skill_slots))]
+struct Player {
+    race: Race,
+    level: u8,
+    skill_slots: u8,
+    #[builder(optional)]
+    nickname: Option<String>,
+}
+
+/// the states of the builder: `PlayerInitial`, `PlayerRaceSet`, `PlayerLevelSet`, `PlayerSkillSlotsSet`
+fn level_up(builder: PlayerBuilder<PlayerRaceSet>) -> PlayerBuilder<PlayerLevelSet> {
+    builder.level(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_builder_works() {
+        let builder: PlayerBuilder<PlayerRaceSet> = Player::builder().race(Race::Human);
+        assert_eq!(
+            <PlayerRaceSet as StateNamePlayerBuilder>::NAME,
+            "PlayerRaceSet"
+        );
+
+        let player = level_up(builder)
+            .nickname("arthas".to_string())
+            .skill_slots(2)
+            .build();
+
+        assert_eq!(
+            player,
+            Player {
+                race: Race::Human,
+                level: 10,
+                skill_slots: 2,
+                nickname: Some("arthas".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn optional_fields_can_be_set_at_any_step() {
+        let player = Player::builder()
+            .nickname("thrall".to_string())
+            .race(Race::Orc)
+            .level(1)
+            .skill_slots(1)
+            .build();
+        assert_eq!(player.nickname.as_deref(), Some("thrall"));
+    }
+
+    // these should not compile, the order is enforced
+    // let player = Player::builder().level(1);
+    // let player = Player::builder().race(Race::Orc).skill_slots(1);
+}
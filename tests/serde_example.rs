@@ -0,0 +1,98 @@
+// run with `cargo test --features serde`
+#![cfg(feature = "serde")]
+
+use serde::{Deserialize, Serialize};
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Race {
+    Orc,
+    Human,
+}
+
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (Initial),
+    transitions = (Initial -> RaceSet, RaceSet -> LevelSet),
+    any = true // `AnyPlayerBuilder` gets the same serde derives, with the state as a tag
+)]
+#[derive(Serialize, Deserialize)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_is_skipped() {
+        let player = PlayerBuilder::new().set_race(Race::Orc);
+        let json = serde_json::to_string(&player).unwrap();
+        assert_eq!(json, r#"{"race":"Orc","level":null}"#);
+
+        let player: PlayerBuilder<RaceSet> = serde_json::from_str(&json).unwrap();
+        assert_eq!(player.race, Some(Race::Orc));
+    }
+
+    #[test]
+    fn typed_structs_trust_the_caller_with_the_state() {
+        // saved in `Initial`, but nothing in the JSON says so:
+        // the typed struct is deserialized in whatever state the caller asks for
+        let json = serde_json::to_string(&PlayerBuilder::new()).unwrap();
+        let player: PlayerBuilder<LevelSet> = serde_json::from_str(&json).unwrap();
+        assert_eq!(player.level, None);
+
+        // going through `AnyPlayerBuilder` checks the state
+        let json = serde_json::to_string(&AnyPlayerBuilder::from(PlayerBuilder::new())).unwrap();
+        let player: AnyPlayerBuilder = serde_json::from_str(&json).unwrap();
+        assert!(player.try_into_level_set().is_err());
+    }
+
+    #[test]
+    fn state_is_tagged_and_validated() {
+        let player: AnyPlayerBuilder = PlayerBuilder::new()
+            .set_race(Race::Human)
+            .set_level(10)
+            .into();
+        let json = serde_json::to_string(&player).unwrap();
+        assert_eq!(json, r#"{"state":"LevelSet","race":"Human","level":10}"#);
+
+        let player: AnyPlayerBuilder = serde_json::from_str(&json).unwrap();
+        assert_eq!(player.state(), PlayerBuilderState::LevelSet);
+
+        // not one of the states of `PlayerBuilder`
+        let result = serde_json::from_str::<AnyPlayerBuilder>(r#"{"state":"Done","race":null}"#);
+        assert!(result.is_err());
+    }
+}
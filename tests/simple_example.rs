@@ -15,7 +15,7 @@ enum Race {
 }
 
 #[derive(Debug)]
-#[type_state(states = (Initial, RaceSet, LevelSet, SkillSlotsSet), slots = (Initial))]
+#[type_state(states = (Initial, RaceSet, LevelSet, SkillSlotsSet), slots = (Initial), state_names = true)]
 struct PlayerBuilder {
     race: Option<Race>,
     level: Option<u8>,
@@ -135,6 +135,16 @@ mod tests {
         assert_eq!(player.skill_slots, another_player.skill_slots);
     }
 
+    #[test]
+    fn state_names_are_reflected() {
+        let player = PlayerBuilder::new();
+        assert_eq!(player.state_names(), ["Initial"]);
+
+        let player = player.set_race(Race::Human).set_level(10);
+        assert_eq!(player.state_names(), ["LevelSet"]);
+        assert_eq!(<LevelSet as StateNamePlayerBuilder>::NAME, "LevelSet");
+    }
+
     #[test]
     fn other_macros_are_preserved() {
         let player = PlayerBuilder::new();
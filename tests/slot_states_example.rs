@@ -0,0 +1,159 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug)]
+struct Player {
+    race: Race,
+    level: u8,
+    spell_slots: u8,
+}
+
+#[derive(Debug, PartialEq)]
+enum Race {
+    #[allow(unused)]
+    Orc,
+    Human,
+}
+
+// each slot only accepts its own states, `states = (...)` can be omitted in that case
+#[type_state(slots = (
+    race: Unset in (Unset, RaceSet),
+    level: Unset in (Unset, LevelSet),
+    spell_slots: Unset in (Unset, SpellSlotsSet),
+))]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+    spell_slots: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(race = Unset, level = Unset, spell_slots = Unset)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+            spell_slots: None,
+        }
+    }
+
+    #[require(race = Unset)]
+    #[switch_to(race = RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+            spell_slots: self.spell_slots,
+        }
+    }
+
+    #[require(race = RaceSet, level = Unset)]
+    #[switch_to(level = LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+            spell_slots: self.spell_slots,
+        }
+    }
+
+    // `spell_slots = LevelSet` would be rejected at compile time
+    #[require(level = LevelSet, spell_slots = Unset)]
+    #[switch_to(spell_slots = SpellSlotsSet)]
+    fn set_spell_slots(self, spell_slots: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: self.level,
+            spell_slots: Some(spell_slots),
+        }
+    }
+
+    /// available at any state, the generics are bounded by their slot's states
+    #[require(A, B, C)]
+    fn say_hi(self) -> Self {
+        println!("Hi!");
+
+        self
+    }
+
+    #[require(race = RaceSet, level = LevelSet, spell_slots = SpellSlotsSet)]
+    fn build(self) -> Player {
+        Player {
+            race: self.race.expect("type safety ensures this is set"),
+            level: self.level.expect("type safety ensures this is set"),
+            spell_slots: self.spell_slots.expect("type safety ensures this is set"),
+        }
+    }
+}
+
+// the sealer of the slot of `Door` must not collide with the sealer of `Door1`
+#[type_state(slots = (Closed in (Closed, Open)))]
+struct Door {
+    opened: u32,
+}
+
+#[impl_state]
+impl Door {
+    #[require(Closed)]
+    fn new() -> Door {
+        Door { opened: 0 }
+    }
+
+    #[require(Closed)]
+    #[switch_to(Open)]
+    fn open(self) -> Door {
+        Door {
+            opened: self.opened + 1,
+        }
+    }
+}
+
+#[type_state(states = (Locked, Unlocked), slots = (Locked))]
+struct Door1 {
+    unlocked: u32,
+}
+
+#[impl_state]
+impl Door1 {
+    #[require(Locked)]
+    fn new() -> Door1 {
+        Door1 { unlocked: 0 }
+    }
+
+    #[require(Locked)]
+    #[switch_to(Unlocked)]
+    fn unlock(self) -> Door1 {
+        Door1 {
+            unlocked: self.unlocked + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_states_player_creation_works() {
+        let player = PlayerBuilder::new()
+            .say_hi()
+            .set_race(Race::Human)
+            .set_level(10)
+            .set_spell_slots(3)
+            .say_hi()
+            .build();
+
+        assert_eq!(player.race, Race::Human);
+        assert_eq!(player.level, 10);
+        assert_eq!(player.spell_slots, 3);
+    }
+
+    #[test]
+    fn slot_sealer_does_not_collide_with_other_struct() {
+        let door: Door<Open> = Door::new().open();
+        let door1: Door1<Unlocked> = Door1::new().unlock();
+
+        assert_eq!(door.opened, 1);
+        assert_eq!(door1.unlocked, 1);
+    }
+}
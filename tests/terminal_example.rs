@@ -0,0 +1,164 @@
+use state_shift::{impl_state, type_state};
+
+#[type_state(
+    states = (Open, Committed, RolledBack),
+    slots = (Open),
+    terminal = (Committed, RolledBack),
+    parts = true
+)]
+#[derive(Clone, Debug, PartialEq)]
+struct Transaction {
+    writes: Vec<String>,
+}
+
+#[impl_state]
+impl Transaction {
+    #[require(Open)]
+    fn begin() -> Transaction {
+        Transaction { writes: Vec::new() }
+    }
+
+    #[require(Open)]
+    fn write(mut self, write: &str) -> Transaction {
+        self.writes.push(write.to_string());
+        self
+    }
+
+    // delegates to the other consuming methods
+    #[require(Open)]
+    fn write_all(self, writes: &[&str]) -> Transaction {
+        let mut transaction = self;
+        for write in writes {
+            transaction = transaction.write(write);
+        }
+        transaction
+    }
+
+    #[require(Open)]
+    #[switch_to(Committed)]
+    fn commit(self) -> Transaction {
+        Transaction {
+            writes: self.writes,
+        }
+    }
+
+    #[require(Open)]
+    #[switch_to(Committed)]
+    fn write_and_commit(self, write: &str) -> Transaction {
+        Transaction::commit(self.write(write))
+    }
+
+    // gives the object back as it is, if there is nothing to do
+    #[require(Open)]
+    #[switch_to(RolledBack, on_err = keep)]
+    fn try_rollback(self) -> Result<Transaction, Transaction> {
+        if self.writes.is_empty() {
+            return Err(self);
+        }
+        Ok(self.rollback())
+    }
+
+    #[require(Open)]
+    #[switch_to(RolledBack)]
+    fn rollback(self) -> Transaction {
+        Transaction { writes: Vec::new() }
+    }
+
+    // the object is finished in a terminal state, so it is not dropped in its state
+    #[require(Open)]
+    #[switch_to(Committed)]
+    fn into_writes(self) -> Vec<String> {
+        self.writes
+    }
+
+    // the object is neither returned nor finished, so it is dropped in its state
+    #[require(Open)]
+    fn id(self) -> u64 {
+        self.writes.len() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_can_be_dropped_in_terminal_states() {
+        let committed = Transaction::begin().write("a").write("b").commit();
+        assert_eq!(committed.writes, vec!["a", "b"]);
+        drop(committed);
+
+        let rolled_back = Transaction::begin().write("a").rollback();
+        assert!(rolled_back.writes.is_empty());
+    }
+
+    #[test]
+    fn objects_can_be_consumed_in_non_terminal_states() {
+        let writes = Transaction::begin().write("a").into_writes();
+        assert_eq!(writes, vec!["a"]);
+
+        let parts = Transaction::begin().write("b").into_parts();
+        assert_eq!(parts.writes, vec!["b"]);
+    }
+
+    #[test]
+    fn clones_have_to_be_consumed_as_well() {
+        let transaction = Transaction::begin().write("a");
+        let copy = transaction.clone();
+        assert_eq!(transaction, copy);
+
+        drop(transaction.commit());
+        drop(copy.rollback());
+    }
+
+    #[test]
+    fn objects_can_be_given_back_and_passed_on() {
+        let committed = Transaction::begin()
+            .write_all(&["a", "b"])
+            .write_and_commit("c");
+        assert_eq!(committed.writes, vec!["a", "b", "c"]);
+
+        let rolled_back = Transaction::begin().write("a").try_rollback().unwrap();
+        assert!(rolled_back.writes.is_empty());
+    }
+
+    // only checked in debug builds
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(
+            expected = "`Transaction` is dropped in the non-terminal state [\"Open\"], it has to end in one of `Committed`, `RolledBack`"
+        )
+    )]
+    fn objects_given_back_are_still_checked() {
+        // nothing to roll back, so the object is given back in `Open`
+        let transaction = Transaction::begin().write_all(&[]);
+        drop(transaction.try_rollback().unwrap_err());
+    }
+
+    // only checked in debug builds
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(
+            expected = "`Transaction` is dropped in the non-terminal state [\"Open\"], it has to end in one of `Committed`, `RolledBack`"
+        )
+    )]
+    fn dropping_in_non_terminal_state_panics() {
+        let transaction = Transaction::begin().write("a");
+        drop(transaction);
+    }
+
+    // only checked in debug builds
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(
+            expected = "`Transaction` is dropped in the non-terminal state [\"Open\"], it has to end in one of `Committed`, `RolledBack`"
+        )
+    )]
+    fn consuming_without_finishing_panics() {
+        let id = Transaction::begin().write("a").id();
+        assert_eq!(id, 1);
+    }
+}
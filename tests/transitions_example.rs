@@ -0,0 +1,101 @@
+use state_shift::{impl_state, type_state};
+
+#[derive(Debug)]
+struct Player {
+    race: Race,
+    level: u8,
+}
+
+#[derive(Debug, PartialEq)]
+enum Race {
+    #[allow(unused)]
+    Orc,
+    Human,
+}
+
+// the state machine lives in one place, `#[impl_state]` rejects any other transition
+#[type_state(
+    states = (Initial, RaceSet, LevelSet),
+    slots = (Initial),
+    transitions = (Initial -> RaceSet, RaceSet -> LevelSet, _ -> Initial)
+)]
+struct PlayerBuilder {
+    race: Option<Race>,
+    level: Option<u8>,
+}
+
+#[impl_state]
+impl PlayerBuilder {
+    #[require(Initial)]
+    fn new() -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    #[require(Initial)]
+    #[switch_to(RaceSet)]
+    fn set_race(self, race: Race) -> PlayerBuilder {
+        PlayerBuilder {
+            race: Some(race),
+            level: self.level,
+        }
+    }
+
+    // `#[require(Initial)]` here would be rejected: `Initial -> LevelSet` is not declared
+    #[require(RaceSet)]
+    #[switch_to(LevelSet)]
+    fn set_level(self, level: u8) -> PlayerBuilder {
+        PlayerBuilder {
+            race: self.race,
+            level: Some(level),
+        }
+    }
+
+    /// allowed from any state, thanks to `_ -> Initial`
+    #[require(A)]
+    #[switch_to(Initial)]
+    fn reset(self) -> PlayerBuilder {
+        PlayerBuilder {
+            race: None,
+            level: None,
+        }
+    }
+
+    /// not a transition, so it doesn't need to be declared
+    #[require(A)]
+    fn say_hi(self) -> Self {
+        println!("Hi!");
+
+        self
+    }
+
+    #[require(LevelSet)]
+    fn build(self) -> Player {
+        Player {
+            race: self.race.expect("type safety ensures this is set"),
+            level: self.level.expect("type safety ensures this is set"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declared_transitions_work() {
+        let player = PlayerBuilder::new()
+            .set_race(Race::Orc)
+            .set_level(3)
+            .reset()
+            .set_race(Race::Human)
+            .say_hi()
+            .set_level(10)
+            .build();
+
+        assert_eq!(player.race, Race::Human);
+        assert_eq!(player.level, 10);
+    }
+}
@@ -82,6 +82,55 @@ mod example {
     }
 }
 
+// The same builder with named slots, in its own module since the states are generated next to the
+// struct
+mod named_example {
+    use state_shift::{impl_state, type_state};
+
+    use super::example::Method;
+
+    #[type_state(states = (Unset, ASet, BSet, AOrBSet), slots = (a: Unset, b: Unset, either: Unset))]
+    pub struct NamedMethodBuilder {
+        #[allow(unused)]
+        slot_a: Option<u8>,
+        slot_b: Option<u8>,
+    }
+
+    #[impl_state]
+    impl NamedMethodBuilder {
+        #[require(a = Unset, b = Unset, either = Unset)] // require the default state for the constructor
+        pub fn new() -> NamedMethodBuilder {
+            NamedMethodBuilder {
+                slot_a: None,
+                slot_b: None,
+            }
+        }
+
+        #[require(a = Unset)] // `b` and `either` can be in any state
+        #[switch_to(a = ASet, either = AOrBSet)] // `b` keeps its state
+        pub fn set_slot_a(self, slot_a: u8) -> NamedMethodBuilder {
+            NamedMethodBuilder {
+                slot_a: Some(slot_a),
+                slot_b: self.slot_b,
+            }
+        }
+
+        #[require(b = Unset)]
+        #[switch_to(b = BSet, either = AOrBSet)]
+        pub fn set_slot_b(self, slot_b: u8) -> NamedMethodBuilder {
+            NamedMethodBuilder {
+                slot_a: self.slot_a,
+                slot_b: Some(slot_b),
+            }
+        }
+
+        #[require(either = AOrBSet)]
+        pub fn build(self) -> Method {
+            Method {}
+        }
+    }
+}
+
 #[test]
 fn test_method_builder() {
     let myparentobj = example::MyParentObject::new();
@@ -91,3 +140,13 @@ fn test_method_builder() {
     let res = meth.start();
     assert!(res.is_ok())
 }
+
+#[test]
+fn test_named_method_builder() {
+    let meth = named_example::NamedMethodBuilder::new()
+        .set_slot_b(7)
+        .set_slot_a(42)
+        .build();
+    let res = meth.start();
+    assert!(res.is_ok())
+}